@@ -7,7 +7,7 @@ extern crate libc;
 use std::mem::size_of;
 
 use glfw::Context;
-use hgl::{Shader, Program, Triangles, Vbo, Vao};
+use hgl::{Shader, ProgramBuilder, Triangles, Vbo, Vao};
 
 static VERTEX_SHADER: &'static str = "
 #version 140
@@ -46,9 +46,11 @@ fn main(argc: int, argv: *const *const u8) -> int {
 
         let vao = Vao::new();
         vao.bind();
-        let program = Program::link([Shader::compile(VERTEX_SHADER, hgl::VertexShader),
-                                     Shader::compile(FRAGMENT_SHADER, hgl::FragmentShader)]).unwrap();
-        program.bind_frag(0, "out_color");
+        let shaders = [Shader::compile(VERTEX_SHADER, hgl::VertexShader),
+                       Shader::compile(FRAGMENT_SHADER, hgl::FragmentShader)];
+        let program = ProgramBuilder::new().shaders(&shaders)
+                                           .frag_data_location("out_color", 0)
+                                           .link().unwrap();
         program.bind();
 
         let vbo = Vbo::from_data([0.0f32,  0.5, 1.0, 0.0, 0.0,
@@ -56,8 +58,8 @@ fn main(argc: int, argv: *const *const u8) -> int {
         -0.5,    -0.5, 0.0, 0.0, 1.0],
         hgl::StaticDraw);
 
-        vao.enable_attrib(&program, "position", gl::FLOAT, 2, 5*size_of::<f32>() as i32, 0);
-        vao.enable_attrib(&program, "color", gl::FLOAT, 3, 5*size_of::<f32>() as i32, 2*size_of::<f32>());
+        vao.enable_attrib(&program, "position", gl::FLOAT, 2, 5*size_of::<f32>() as i32, 0).unwrap();
+        vao.enable_attrib(&program, "color", gl::FLOAT, 3, 5*size_of::<f32>() as i32, 2*size_of::<f32>()).unwrap();
         vbo.bind();
 
         while !window.should_close() {
@@ -1,28 +1,83 @@
 use gl;
 use std;
 use libc::c_void;
-use gl::types::{GLuint, GLenum, GLsizeiptr};
+use std::mem;
+use std::raw;
+use gl::types::{GLint, GLuint, GLenum, GLbitfield, GLintptr, GLsizeiptr};
 
-/// A vertex buffer object
-pub struct Vbo {
-    pub name: GLuint
+use debug;
+use error;
+use error::{Error, InvalidArgument};
+use std140::{Std140, Std140Writer};
+
+/// The binding points a buffer can be bound to.
+pub enum BufferTarget {
+    /// Vertex attributes
+    ArrayTarget,
+    /// Vertex indices
+    ElementArrayTarget,
+    /// Uniform blocks
+    UniformTarget,
+    /// Destination of glReadPixels and glGetTexImage
+    PixelPackTarget,
+    /// Source of glTexImage* and glTexSubImage*
+    PixelUnpackTarget,
+    /// Storage of buffer textures
+    TextureBufferTarget,
+    /// Source of glCopyBufferSubData
+    CopyReadTarget,
+    /// Destination of glCopyBufferSubData
+    CopyWriteTarget,
+    /// Transform feedback output
+    TransformFeedbackTarget,
+    /// Parameters of glDraw*Indirect. Requires GL 4.0.
+    DrawIndirectTarget,
+    /// Shader storage blocks. Requires GL 4.3.
+    ShaderStorageTarget,
 }
 
-impl Drop for Vbo {
-    fn drop(&mut self) {
-        unsafe { gl::DeleteBuffers(1, &self.name); }
+impl BufferTarget {
+    pub fn to_glenum(&self) -> GLenum {
+        match *self {
+            ArrayTarget => gl::ARRAY_BUFFER,
+            ElementArrayTarget => gl::ELEMENT_ARRAY_BUFFER,
+            UniformTarget => gl::UNIFORM_BUFFER,
+            PixelPackTarget => gl::PIXEL_PACK_BUFFER,
+            PixelUnpackTarget => gl::PIXEL_UNPACK_BUFFER,
+            TextureBufferTarget => gl::TEXTURE_BUFFER,
+            CopyReadTarget => gl::COPY_READ_BUFFER,
+            CopyWriteTarget => gl::COPY_WRITE_BUFFER,
+            TransformFeedbackTarget => gl::TRANSFORM_FEEDBACK_BUFFER,
+            DrawIndirectTarget => gl::DRAW_INDIRECT_BUFFER,
+            ShaderStorageTarget => gl::SHADER_STORAGE_BUFFER,
+        }
     }
 }
 
-/// Frequency with which the vbo is expected to be updated
+/// Frequency with which the vbo is expected to be updated, and what the
+/// data is used for. Draw data is written by the application and read by
+/// GL, read data is written by GL and read by the application, and copy
+/// data is written and read by GL.
+#[deriving(Clone, PartialEq, Show)]
 pub enum BufferUsage {
     /// Updated once, drawn many times
     StaticDraw,
     /// Updated many times, drawn many times
     DynamicDraw,
     /// Updated once, drawn once
-    StreamDraw
-    // TODO: add Read and Copy variants
+    StreamDraw,
+    /// Written once by GL, read many times
+    StaticRead,
+    /// Written many times by GL, read many times
+    DynamicRead,
+    /// Written once by GL, read once
+    StreamRead,
+    /// Written once by GL, used by GL many times
+    StaticCopy,
+    /// Written many times by GL, used by GL many times
+    DynamicCopy,
+    /// Written once by GL, used by GL once
+    StreamCopy,
 }
 
 impl BufferUsage {
@@ -31,72 +86,573 @@ impl BufferUsage {
             StaticDraw  => gl::STATIC_DRAW,
             DynamicDraw => gl::DYNAMIC_DRAW,
             StreamDraw  => gl::STREAM_DRAW,
+            StaticRead  => gl::STATIC_READ,
+            DynamicRead => gl::DYNAMIC_READ,
+            StreamRead  => gl::STREAM_READ,
+            StaticCopy  => gl::STATIC_COPY,
+            DynamicCopy => gl::DYNAMIC_COPY,
+            StreamCopy  => gl::STREAM_COPY,
+        }
+    }
+
+    /// The usage `usage` names, or None if it isn't a usage hint.
+    pub fn from_glenum(usage: GLenum) -> Option<BufferUsage> {
+        match usage {
+            gl::STATIC_DRAW  => Some(StaticDraw),
+            gl::DYNAMIC_DRAW => Some(DynamicDraw),
+            gl::STREAM_DRAW  => Some(StreamDraw),
+            gl::STATIC_READ  => Some(StaticRead),
+            gl::DYNAMIC_READ => Some(DynamicRead),
+            gl::STREAM_READ  => Some(StreamRead),
+            gl::STATIC_COPY  => Some(StaticCopy),
+            gl::DYNAMIC_COPY => Some(DynamicCopy),
+            gl::STREAM_COPY  => Some(StreamCopy),
+            _ => None
         }
     }
 }
 
-impl Vbo {
-    /// Generate a new VBO, without binding it.
-    pub fn new() -> Vbo {
-        let mut vbo: GLuint = 0;
-        unsafe { gl::GenBuffers(1, &mut vbo as *mut GLuint); }
-        Vbo { name: vbo }
+/// How a buffer is mapped by `map_range`.
+///
+/// Note that this struct uses the builder pattern:
+///
+///     let access = MapAccess::write().invalidate_range().explicit_flush();
+pub struct MapAccess {
+    bits: GLbitfield,
+}
+
+impl MapAccess {
+    /// Map for reading.
+    pub fn read() -> MapAccess {
+        MapAccess { bits: gl::MAP_READ_BIT }
     }
 
-    /// Generate a new VBO and upload `data` to it.
-    pub fn from_data<T>(data: &[T], usage: BufferUsage) -> Vbo {
-        let vbo = Vbo::new();
-        vbo.load_data(data, usage);
-        vbo
+    /// Map for writing.
+    pub fn write() -> MapAccess {
+        MapAccess { bits: gl::MAP_WRITE_BIT }
+    }
+
+    /// Map for reading and writing.
+    pub fn read_write() -> MapAccess {
+        MapAccess { bits: gl::MAP_READ_BIT | gl::MAP_WRITE_BIT }
+    }
+
+    /// The previous contents of the mapped range may be discarded. Not
+    /// allowed with reading.
+    pub fn invalidate_range(self) -> MapAccess {
+        MapAccess { bits: self.bits | gl::MAP_INVALIDATE_RANGE_BIT }
+    }
+
+    /// The previous contents of the whole buffer may be discarded. Not
+    /// allowed with reading.
+    pub fn invalidate_buffer(self) -> MapAccess {
+        MapAccess { bits: self.bits | gl::MAP_INVALIDATE_BUFFER_BIT }
+    }
+
+    /// Don't wait for GL to finish using the buffer. Writing to data GL is
+    /// still reading gives undefined results.
+    pub fn unsynchronized(self) -> MapAccess {
+        MapAccess { bits: self.bits | gl::MAP_UNSYNCHRONIZED_BIT }
+    }
+
+    /// Writes are only guaranteed to reach the buffer once passed to
+    /// `Mapping::flush_range`. Requires writing.
+    pub fn explicit_flush(self) -> MapAccess {
+        MapAccess { bits: self.bits | gl::MAP_FLUSH_EXPLICIT_BIT }
+    }
+
+    pub fn to_glbitfield(&self) -> GLbitfield {
+        self.bits
+    }
+}
+
+/// A range of a buffer mapped into memory as `T`s, which is unmapped when
+/// this is dropped. The buffer can't be used by GL while it is mapped, and
+/// its data store can't be replaced, since that takes `&mut Buffer`. `T`
+/// must be `Copy`, as the mapped bytes are whatever GL put there.
+///
+/// Whether the mapped memory may be read or written depends on the
+/// `MapAccess` it was mapped with, so it is reached through `as_slice` and
+/// `as_mut_slice`, which check, rather than `Deref` (which can't fail, and
+/// can't yet target a slice).
+pub struct Mapping<'a, T: Copy> {
+    buffer: &'a Buffer,
+    ptr: *mut T,
+    len: uint,
+    access: MapAccess,
+}
+
+#[unsafe_destructor]
+impl<'a, T: Copy> Drop for Mapping<'a, T> {
+    fn drop(&mut self) {
+        self.buffer.bind();
+        // a false return means the contents were lost (eg on a mode
+        // switch); there is nothing useful to do about it here.
+        gl::UnmapBuffer(self.buffer.target);
+    }
+}
+
+impl<'a, T: Copy> Mapping<'a, T> {
+    /// Fail with `msg` unless this was mapped with `bit`.
+    fn require(&self, bit: GLbitfield, msg: &str) -> Result<(), Error> {
+        if self.access.bits & bit == 0 {
+            return Err(InvalidArgument(msg.to_string()));
+        }
+        Ok(())
+    }
+
+    /// The mapped `T`s. Fails unless mapped for reading.
+    pub fn as_slice<'b>(&'b self) -> Result<&'b [T], Error> {
+        try!(self.require(gl::MAP_READ_BIT, "mapping is not readable"));
+        Ok(unsafe { mem::transmute(raw::Slice { data: self.ptr as *const T, len: self.len }) })
+    }
+
+    /// The mapped `T`s, for writing. Fails unless mapped for writing.
+    pub fn as_mut_slice<'b>(&'b mut self) -> Result<&'b mut [T], Error> {
+        try!(self.require(gl::MAP_WRITE_BIT, "mapping is not writable"));
+        Ok(unsafe { mem::transmute(raw::Slice { data: self.ptr as *const T, len: self.len }) })
+    }
+
+    /// The number of `T`s mapped.
+    pub fn len(&self) -> uint {
+        self.len
     }
 
-    /// Load data into this vbo.
-    pub fn load_data<T>(&self, data: &[T], usage: BufferUsage) {
+    /// Make writes to `len` `T`s starting `offset` into the mapping visible
+    /// to GL. Fails unless mapped with `MapAccess::explicit_flush`.
+    pub fn flush_range(&self, offset: uint, len: uint) -> Result<(), Error> {
+        try!(self.require(gl::MAP_FLUSH_EXPLICIT_BIT, "mapping was not made with explicit_flush"));
+        try!(check_range(offset, len, self.len));
+        let size = mem::size_of::<T>();
+        self.buffer.bind();
+        gl::FlushMappedBufferRange(self.buffer.target, (offset * size) as GLintptr,
+                                   (len * size) as GLsizeiptr);
+        error::check("Mapping::flush_range")
+    }
+}
+
+/// A buffer object. `target` is where `bind` and `load_data` bind it, but
+/// the same buffer can be bound anywhere else with `bind_to`.
+pub struct Buffer {
+    pub name: GLuint,
+    pub target: GLenum,
+}
+
+impl Drop for Buffer {
+    fn drop(&mut self) {
+        unsafe { gl::DeleteBuffers(1, &self.name); }
+    }
+}
+
+impl Buffer {
+    /// Generate a new buffer, without binding it.
+    pub fn new(target: BufferTarget) -> Buffer {
+        let mut buf: GLuint = 0;
+        unsafe { gl::GenBuffers(1, &mut buf as *mut GLuint); }
+        error::check_or_fail("Buffer::new");
+        Buffer { name: buf, target: target.to_glenum() }
+    }
+
+    /// Generate a new buffer and upload `data` to it.
+    pub fn from_data<T>(target: BufferTarget, data: &[T], usage: BufferUsage) -> Buffer {
+        let mut buf = Buffer::new(target);
+        buf.load_data(data, usage);
+        buf
+    }
+
+    /// Load data into this buffer, replacing its previous contents. This
+    /// gives the buffer a new data store, so it takes `&mut self` to rule
+    /// out a live `Mapping` of the old one.
+    pub fn load_data<T>(&mut self, data: &[T], usage: BufferUsage) {
         self.bind();
         unsafe {
-            gl::BufferData(gl::ARRAY_BUFFER,
-                           (data.len() * std::mem::size_of::<T>()) as GLsizeiptr,
+            gl::BufferData(self.target,
+                           (data.len() * mem::size_of::<T>()) as GLsizeiptr,
                            data.as_ptr() as *const c_void, usage.to_glenum());
         }
+        error::check_or_fail("Buffer::load_data");
+    }
+
+    /// Reserve room for `len` `T`s, replacing the previous contents with
+    /// undefined ones. Like `load_data`, this gives the buffer a new data
+    /// store.
+    pub fn allocate<T>(&mut self, len: uint, usage: BufferUsage) {
+        self.bind();
+        unsafe {
+            gl::BufferData(self.target, (len * mem::size_of::<T>()) as GLsizeiptr,
+                           std::ptr::null(), usage.to_glenum());
+        }
+        error::check_or_fail("Buffer::allocate");
+    }
+
+    /// Overwrite part of this buffer with `data`, starting `offset` `T`s in,
+    /// without reallocating it. Fails if that would write past the end of
+    /// the buffer.
+    pub fn update_range<T>(&self, offset: uint, data: &[T]) -> Result<(), Error> {
+        let len = try!(self.len_of::<T>());
+        try!(check_range(offset, data.len(), len));
+        self.write_range(offset, data);
+        error::check("Buffer::update_range")
+    }
+
+    /// Map `len` `T`s starting `offset` `T`s into the buffer, for reading or
+    /// writing directly. Fails if the range is outside the buffer, or GL
+    /// refuses to map it.
+    pub fn map_range<'a, T: Copy>(&'a self, offset: uint, len: uint,
+                            access: MapAccess) -> Result<Mapping<'a, T>, Error> {
+        let size = try!(self.len_of::<T>());
+        try!(check_range(offset, len, size));
+        self.map(offset, len, access)
+    }
+
+    /// How many whole `T`s fit in this buffer. Fails if `T` is zero-sized,
+    /// since ranges of those can't be told apart.
+    fn len_of<T>(&self) -> Result<uint, Error> {
+        match mem::size_of::<T>() {
+            0 => Err(InvalidArgument("can't address a buffer in zero-sized values".to_string())),
+            size => Ok(self.size() / size)
+        }
+    }
+
+    /// glMapBufferRange, with `offset` and `len` counted in `T`s.
+    fn map<'a, T: Copy>(&'a self, offset: uint, len: uint,
+                  access: MapAccess) -> Result<Mapping<'a, T>, Error> {
+        if access.bits & (gl::MAP_READ_BIT | gl::MAP_WRITE_BIT) == 0 {
+            return Err(InvalidArgument("mapping must be for reading or writing".to_string()));
+        }
+        let size = mem::size_of::<T>();
+        self.bind();
+        let ptr = gl::MapBufferRange(self.target, (offset * size) as GLintptr,
+                                     (len * size) as GLsizeiptr, access.bits);
+        try!(error::check("Buffer::map_range"));
+        if ptr.is_null() {
+            return Err(InvalidArgument("glMapBufferRange failed".to_string()));
+        }
+        Ok(Mapping { buffer: self, ptr: ptr as *mut T, len: len, access: access })
+    }
+
+    /// Copy `len` `T`s starting `src_offset` `T`s into `other` to
+    /// `dst_offset` `T`s into this buffer, without the data leaving GL.
+    /// Copying within one buffer is allowed if the ranges don't overlap.
+    /// This binds both buffers to the copy targets. Fails if either range
+    /// is outside its buffer, or the ranges overlap.
+    pub fn copy_from<T>(&self, other: &Buffer, src_offset: uint, dst_offset: uint,
+                        len: uint) -> Result<(), Error> {
+        try!(check_range(src_offset, len, try!(other.len_of::<T>())));
+        try!(check_range(dst_offset, len, try!(self.len_of::<T>())));
+        let size = mem::size_of::<T>();
+        try!(self.copy_bytes(other, src_offset * size, dst_offset * size, len * size));
+        error::check("Buffer::copy_from")
+    }
+
+    /// Read `len` `T`s starting `offset` `T`s into the buffer back from GL,
+    /// for example to check transform feedback or compute shader output.
+    /// This waits for GL to finish writing them. `T` must be `Copy`, as the
+    /// bytes are whatever GL put there. Fails if the range is outside the
+    /// buffer.
+    pub fn read_back<T: Copy>(&self, offset: uint, len: uint) -> Result<Vec<T>, Error> {
+        let size = try!(self.len_of::<T>());
+        try!(check_range(offset, len, size));
+        let data = self.read_range(offset, len);
+        try!(error::check("Buffer::read_back"));
+        Ok(data)
+    }
+
+    /// glCopyBufferSubData, with everything counted in bytes. Fails if
+    /// copying between overlapping ranges of the same buffer.
+    fn copy_bytes(&self, other: &Buffer, src_offset: uint, dst_offset: uint,
+                  len: uint) -> Result<(), Error> {
+        if other.name == self.name && src_offset < dst_offset + len && dst_offset < src_offset + len {
+            return Err(InvalidArgument(format!("copying {} bytes from {} to {} overlaps within \
+                                                one buffer", len, src_offset, dst_offset)));
+        }
+        other.bind_to(CopyReadTarget);
+        self.bind_to(CopyWriteTarget);
+        gl::CopyBufferSubData(gl::COPY_READ_BUFFER, gl::COPY_WRITE_BUFFER,
+                              src_offset as GLintptr, dst_offset as GLintptr, len as GLsizeiptr);
+        Ok(())
+    }
+
+    /// glGetBufferSubData, with `offset` and `len` counted in `T`s.
+    fn read_range<T: Copy>(&self, offset: uint, len: uint) -> Vec<T> {
+        let size = mem::size_of::<T>();
+        let mut data: Vec<T> = Vec::with_capacity(len);
+        self.bind();
+        unsafe {
+            gl::GetBufferSubData(self.target, (offset * size) as GLintptr, (len * size) as GLsizeiptr,
+                                 data.as_mut_slice().as_mut_ptr() as *mut c_void);
+            data.set_len(len);
+        }
+        data
+    }
+
+    /// glBufferSubData, with `offset` counted in `T`s.
+    fn write_range<T>(&self, offset: uint, data: &[T]) {
+        let size = mem::size_of::<T>();
+        self.bind();
+        unsafe {
+            gl::BufferSubData(self.target, (offset * size) as GLintptr,
+                              (data.len() * size) as GLsizeiptr, data.as_ptr() as *const c_void);
+        }
     }
 
     pub fn bind(&self) {
-        gl::BindBuffer(gl::ARRAY_BUFFER, self.name);
+        gl::BindBuffer(self.target, self.name);
+        error::check_or_fail("Buffer::bind");
+    }
+
+    /// Bind this buffer to `target` instead of its own target, for example
+    /// to read pixels into a vertex buffer.
+    pub fn bind_to(&self, target: BufferTarget) {
+        gl::BindBuffer(target.to_glenum(), self.name);
+        error::check_or_fail("Buffer::bind_to");
+    }
+
+    /// Bind this buffer to binding point `index` of its target, which must
+    /// be an indexed target (uniform, transform feedback or shader storage).
+    pub fn bind_base(&self, index: GLuint) {
+        gl::BindBufferBase(self.target, index, self.name);
+        error::check_or_fail("Buffer::bind_base");
+    }
+
+    /// As `bind_base`, but only binding `size` bytes starting at `offset`.
+    /// For uniform buffers `offset` must be a multiple of
+    /// `gl::UNIFORM_BUFFER_OFFSET_ALIGNMENT`.
+    pub fn bind_range(&self, index: GLuint, offset: uint, size: uint) {
+        gl::BindBufferRange(self.target, index, self.name, offset as GLintptr,
+                            size as GLsizeiptr);
+        error::check_or_fail("Buffer::bind_range");
+    }
+
+    /// Label this buffer for debug messages and debuggers. This binds it.
+    /// Requires GL 4.3 or KHR_debug.
+    pub fn label(&self, label: &str) {
+        self.bind();
+        debug::object_label(gl::BUFFER, self.name, label);
+    }
+
+    fn parameter(&self, pname: GLenum) -> GLint {
+        self.bind();
+        let mut value = 0;
+        unsafe { gl::GetBufferParameteriv(self.target, pname, &mut value); }
+        error::check_or_fail("Buffer::parameter");
+        value
+    }
+
+    /// The size of this buffer's data store in bytes, as GL reports it.
+    /// This binds the buffer.
+    pub fn size(&self) -> uint {
+        self.parameter(gl::BUFFER_SIZE) as uint
+    }
+
+    /// The usage hint this buffer's data store was allocated with, as GL
+    /// reports it, or None if GL reports one this crate doesn't know. This
+    /// binds the buffer.
+    pub fn usage(&self) -> Option<BufferUsage> {
+        BufferUsage::from_glenum(self.parameter(gl::BUFFER_USAGE) as GLenum)
+    }
+}
+
+/// Check that `count` elements starting at `offset` fit in `len`.
+fn check_range(offset: uint, count: uint, len: uint) -> Result<(), Error> {
+    if offset > len || count > len - offset {
+        return Err(InvalidArgument(format!("range of {} elements at {} is outside a buffer of {}",
+                                           count, offset, len)));
+    }
+    Ok(())
+}
+
+/// A buffer holding `len` values of type `T`, uploaded with a known usage.
+///
+/// Remembering the length lets draw calls check that they stay inside the
+/// buffer (see `Vao::draw_buffer`), which is only meaningful when each `T`
+/// is a whole vertex (or index).
+///
+/// It derefs to `&Buffer` for binding, labelling and the like, but not to
+/// `&mut Buffer`, so the data store can only be replaced through
+/// `load_data` and `allocate` here, which keep `len` up to date.
+pub struct TypedBuffer<T> {
+    buffer: Buffer,
+    len: uint,
+    usage: Option<BufferUsage>,
+}
+
+impl<T> Deref<Buffer> for TypedBuffer<T> {
+    fn deref<'a>(&'a self) -> &'a Buffer {
+        &self.buffer
+    }
+}
+
+impl<T> TypedBuffer<T> {
+    /// Generate a new, empty buffer, without binding it.
+    pub fn new(target: BufferTarget) -> TypedBuffer<T> {
+        TypedBuffer { buffer: Buffer::new(target), len: 0, usage: None }
+    }
+
+    /// Generate a new buffer and upload `data` to it.
+    pub fn from_data(target: BufferTarget, data: &[T], usage: BufferUsage) -> TypedBuffer<T> {
+        let mut buf = TypedBuffer::new(target);
+        buf.load_data(data, usage);
+        buf
+    }
+
+    /// Load data into this buffer, replacing its previous contents.
+    pub fn load_data(&mut self, data: &[T], usage: BufferUsage) {
+        self.buffer.load_data(data, usage);
+        self.len = data.len();
+        self.usage = Some(usage);
+    }
+
+    /// Reserve room for `len` `T`s, replacing the previous contents with
+    /// undefined ones.
+    pub fn allocate(&mut self, len: uint, usage: BufferUsage) {
+        self.buffer.allocate::<T>(len, usage);
+        self.len = len;
+        self.usage = Some(usage);
+    }
+
+    /// Overwrite part of this buffer with `data`, starting `offset` `T`s in,
+    /// without reallocating it. Fails if that would write past `len`.
+    pub fn update_range(&self, offset: uint, data: &[T]) -> Result<(), Error> {
+        try!(check_range(offset, data.len(), self.len));
+        self.buffer.write_range(offset, data);
+        error::check("TypedBuffer::update_range")
+    }
+
+    /// Copy `len` `T`s starting at `src_offset` in `other` to `dst_offset`
+    /// in this buffer, without the data leaving GL. Fails if either range is
+    /// outside its buffer's `len`, or they overlap within one buffer.
+    pub fn copy_from(&self, other: &TypedBuffer<T>, src_offset: uint, dst_offset: uint,
+                     len: uint) -> Result<(), Error> {
+        try!(check_range(src_offset, len, other.len));
+        try!(check_range(dst_offset, len, self.len));
+        let size = mem::size_of::<T>();
+        try!(self.buffer.copy_bytes(&other.buffer, src_offset * size, dst_offset * size,
+                                    len * size));
+        error::check("TypedBuffer::copy_from")
+    }
+
+    /// The number of `T`s in this buffer.
+    pub fn len(&self) -> uint {
+        self.len
+    }
+
+    /// The usage given when the data was last loaded, or None if it never
+    /// was. Unlike `usage`, this doesn't ask GL.
+    pub fn loaded_usage(&self) -> Option<BufferUsage> {
+        self.usage
+    }
+}
+
+impl<T: Copy> TypedBuffer<T> {
+    /// Map `len` `T`s starting at `offset`, for reading or writing directly.
+    /// Fails if the range is outside `len`, or GL refuses to map it.
+    pub fn map_range<'a>(&'a self, offset: uint, len: uint,
+                         access: MapAccess) -> Result<Mapping<'a, T>, Error> {
+        try!(check_range(offset, len, self.len));
+        self.buffer.map(offset, len, access)
+    }
+
+    /// Read `len` `T`s starting at `offset` back from GL. Fails if the range
+    /// is outside `len`.
+    pub fn read_back(&self, offset: uint, len: uint) -> Result<Vec<T>, Error> {
+        try!(check_range(offset, len, self.len));
+        let data = self.buffer.read_range(offset, len);
+        try!(error::check("TypedBuffer::read_back"));
+        Ok(data)
+    }
+}
+
+/// A vertex buffer object, aka GL_ARRAY_BUFFER.
+pub struct Vbo {
+    buffer: Buffer
+}
+
+impl Deref<Buffer> for Vbo {
+    fn deref<'a>(&'a self) -> &'a Buffer {
+        &self.buffer
+    }
+}
+
+impl DerefMut<Buffer> for Vbo {
+    fn deref_mut<'a>(&'a mut self) -> &'a mut Buffer {
+        &mut self.buffer
+    }
+}
+
+impl Vbo {
+    /// Generate a new VBO, without binding it.
+    pub fn new() -> Vbo {
+        Vbo { buffer: Buffer::new(ArrayTarget) }
+    }
+
+    /// Generate a new VBO and upload `data` to it.
+    pub fn from_data<T>(data: &[T], usage: BufferUsage) -> Vbo {
+        Vbo { buffer: Buffer::from_data(ArrayTarget, data, usage) }
     }
 }
 
 /// An Element Buffer Object, aka GL_ELEMENT_ARRAY_BUFFER.
 pub struct Ebo {
-    pub name: GLuint
+    buffer: Buffer
+}
+
+impl Deref<Buffer> for Ebo {
+    fn deref<'a>(&'a self) -> &'a Buffer {
+        &self.buffer
+    }
+}
+
+impl DerefMut<Buffer> for Ebo {
+    fn deref_mut<'a>(&'a mut self) -> &'a mut Buffer {
+        &mut self.buffer
+    }
 }
 
 impl Ebo {
     /// Create a new EBO, without binding it.
     pub fn new() -> Ebo {
-        let mut ebo = 0;
-        unsafe { gl::GenBuffers(1, &mut ebo); }
-        Ebo { name: ebo }
+        Ebo { buffer: Buffer::new(ElementArrayTarget) }
     }
 
     /// Create an EBO from a slice of indices.
     pub fn from_indices(indices: &[GLuint]) -> Ebo {
-        let ebo = Ebo::new();
-        ebo.load_data(indices, DynamicDraw);
-        ebo
+        Ebo { buffer: Buffer::from_data(ElementArrayTarget, indices, DynamicDraw) }
     }
+}
 
-    /// Load new index data into this EBO.
-    pub fn load_data<T>(&self, data: &[T], usage: BufferUsage) {
-        self.bind();
-        unsafe {
-            gl::BufferData(gl::ELEMENT_ARRAY_BUFFER,
-                           (data.len() * std::mem::size_of::<T>()) as GLsizeiptr,
-                           data.as_ptr() as *const c_void, usage.to_glenum());
-        }
-    }
+/// A uniform buffer object, holding a `T` packed in std140 layout.
+///
+/// Bind it to a binding point with `bind_base`, and point a program's
+/// uniform block at the same binding point with
+/// `Program::bind_uniform_block`.
+pub struct UniformBuffer<T> {
+    buffer: Buffer
+}
 
-    pub fn bind(&self) {
-        gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, self.name);
+impl<T> Deref<Buffer> for UniformBuffer<T> {
+    fn deref<'a>(&'a self) -> &'a Buffer {
+        &self.buffer
     }
 }
 
+impl<T: Std140> UniformBuffer<T> {
+    /// Create a new UBO, without binding it.
+    pub fn new() -> UniformBuffer<T> {
+        UniformBuffer { buffer: Buffer::new(UniformTarget) }
+    }
+
+    /// Create a UBO holding `value`.
+    pub fn from_data(value: &T, usage: BufferUsage) -> UniformBuffer<T> {
+        let mut ubo = UniformBuffer::new();
+        ubo.load_data(value, usage);
+        ubo
+    }
+
+    /// Pack `value` and upload it to this UBO.
+    pub fn load_data(&mut self, value: &T, usage: BufferUsage) {
+        let mut w = Std140Writer::new();
+        w.write(value);
+        self.buffer.load_data(w.as_slice(), usage);
+    }
+}
@@ -0,0 +1,95 @@
+//! Caching linked program binaries on disk, to skip compiling and linking
+//! shaders on later runs.
+
+use gl;
+use gl::types::GLenum;
+use std::hash;
+use std::io::{fs, File, IoResult, USER_RWX};
+
+use context;
+use error::{Error, Io};
+use program::{Program, ProgramBuilder, Shader, ShaderType, Preprocessor};
+
+/// A directory of program binaries.
+///
+/// Each binary is keyed by a hash of the preprocessed shader sources, the
+/// defines, and the GL renderer and version strings, so a driver update or
+/// a different GPU never sees a stale binary.
+pub struct ProgramCache {
+    dir: Path,
+}
+
+fn load(path: &Path) -> IoResult<(GLenum, Vec<u8>)> {
+    let mut f = try!(File::open(path));
+    let format = try!(f.read_le_u32());
+    let data = try!(f.read_to_end());
+    Ok((format, data))
+}
+
+fn store(path: &Path, format: GLenum, data: &[u8]) -> IoResult<()> {
+    let mut f = try!(File::create(path));
+    try!(f.write_le_u32(format));
+    f.write(data)
+}
+
+impl ProgramCache {
+    /// Use `dir` as the cache, creating it if it doesn't exist.
+    pub fn new(dir: Path) -> Result<ProgramCache, Error> {
+        if !dir.is_dir() {
+            try!(fs::mkdir_recursive(&dir, USER_RWX).map_err(Io));
+        }
+        Ok(ProgramCache { dir: dir })
+    }
+
+    /// Build a program from `stages`, which are pairs of a file path and the
+    /// type of shader it contains, preprocessing each with `pre`.
+    ///
+    /// If a binary for these sources is cached and the driver accepts it, no
+    /// shaders are compiled. Otherwise the program is linked from source and
+    /// its binary saved for next time. Failing to write the cache is not an
+    /// error. When the context doesn't support program binaries, this just
+    /// links from source.
+    pub fn link(&self, stages: &[(&str, ShaderType)],
+                pre: &Preprocessor) -> Result<Program, Error> {
+        let mut sources = Vec::with_capacity(stages.len());
+        for &(path, type_) in stages.iter() {
+            sources.push((try!(pre.process_file(&Path::new(path))), type_));
+        }
+        let types: Vec<ShaderType> = stages.iter().map(|&(_, t)| t).collect();
+
+        if !context::supports_program_binary() {
+            let shaders: Vec<Result<Shader, Error>> =
+                sources.iter().map(|&(ref s, t)| Shader::compile_source(s, t)).collect();
+            return Program::link(shaders.as_slice());
+        }
+
+        let key = {
+            let texts: Vec<(&str, GLenum)> =
+                sources.iter().map(|&(ref s, t)| (s.text.as_slice(), t.to_glenum())).collect();
+            hash::hash(&(texts, pre.defines(),
+                         context::get_string(gl::RENDERER),
+                         context::get_string(gl::VERSION)))
+        };
+        let path = self.dir.join(format!("{:016x}.bin", key));
+
+        match load(&path) {
+            Ok((format, data)) => match Program::from_binary(format, data.as_slice(), types.as_slice()) {
+                Ok(program) => return Ok(program),
+                // rejected; relink and overwrite it below
+                Err(_) => {}
+            },
+            Err(_) => {}
+        }
+
+        let shaders: Vec<Result<Shader, Error>> =
+            sources.iter().map(|&(ref s, t)| Shader::compile_source(s, t)).collect();
+        let program = try!(ProgramBuilder::new().shaders(shaders.as_slice())
+                                                .binary_retrievable(true)
+                                                .link());
+        match program.binary() {
+            Some((format, data)) => { let _ = store(&path, format, data.as_slice()); },
+            None => {}
+        }
+        Ok(program)
+    }
+}
@@ -0,0 +1,54 @@
+//! Querying what the current GL context supports.
+
+use gl;
+use gl::types::{GLint, GLuint, GLenum};
+use libc::c_char;
+use std::c_str::CString;
+
+/// The (major, minor) version of the current context.
+pub fn version() -> (GLint, GLint) {
+    let mut major = 0;
+    let mut minor = 0;
+    unsafe {
+        gl::GetIntegerv(gl::MAJOR_VERSION, &mut major);
+        gl::GetIntegerv(gl::MINOR_VERSION, &mut minor);
+    }
+    (major, minor)
+}
+
+/// Whether the current context is at least version `major.minor`.
+pub fn supports_version(major: GLint, minor: GLint) -> bool {
+    version() >= (major, minor)
+}
+
+/// Whether the current context exposes the extension `name`, eg
+/// `"GL_ARB_compute_shader"`.
+pub fn has_extension(name: &str) -> bool {
+    let mut num = 0;
+    unsafe { gl::GetIntegerv(gl::NUM_EXTENSIONS, &mut num); }
+
+    range(0, num as GLuint).any(|i| {
+        let ext = unsafe {
+            CString::new(gl::GetStringi(gl::EXTENSIONS, i) as *const c_char, false)
+        };
+        ext.as_bytes_no_nul() == name.as_bytes()
+    })
+}
+
+/// A string describing the current context, such as `gl::RENDERER`.
+pub fn get_string(name: GLenum) -> String {
+    let s = unsafe { CString::new(gl::GetString(name) as *const c_char, false) };
+    s.as_str().unwrap_or("").to_string()
+}
+
+/// Whether program binaries can be saved and restored with
+/// `Program::binary` and `Program::from_binary`.
+pub fn supports_program_binary() -> bool {
+    if !supports_version(4, 1) && !has_extension("GL_ARB_get_program_binary") {
+        return false;
+    }
+    // drivers may support the extension without supporting any formats.
+    let mut formats = 0;
+    unsafe { gl::GetIntegerv(gl::NUM_PROGRAM_BINARY_FORMATS, &mut formats); }
+    formats > 0
+}
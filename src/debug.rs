@@ -0,0 +1,307 @@
+//! Debug output (KHR_debug): receiving messages from the driver, debug
+//! groups, and object labels. Requires GL 4.3 or KHR_debug.
+//!
+//! Drivers usually only say much in a debug context, so create one when you
+//! want useful messages.
+//!
+//!     let mut output = debug::install(|msg| println!("{}", msg)).unwrap();
+//!     output.min_severity(debug::LowSeverity);
+//!
+//! Messages stop being delivered when the returned `DebugOutput` is dropped.
+
+use gl;
+use libc;
+use libc::{c_char, c_void};
+use std::c_str::CString;
+use std::cell::RefCell;
+use std::fmt;
+use std::ptr;
+use std::task;
+use gl::types::{GLenum, GLuint, GLsizei, GLchar};
+
+use context;
+use error;
+use error::{Error, MissingExtension};
+
+/// Whether the current context supports debug output.
+pub fn is_supported() -> bool {
+    context::supports_version(4, 3) || context::has_extension("GL_KHR_debug")
+}
+
+/// What produced a debug message.
+#[deriving(Clone, PartialEq, Show)]
+pub enum DebugSource {
+    ApiSource,
+    WindowSystemSource,
+    ShaderCompilerSource,
+    ThirdPartySource,
+    ApplicationSource,
+    OtherSource,
+}
+
+impl DebugSource {
+    pub fn from_glenum(source: GLenum) -> DebugSource {
+        match source {
+            gl::DEBUG_SOURCE_API => ApiSource,
+            gl::DEBUG_SOURCE_WINDOW_SYSTEM => WindowSystemSource,
+            gl::DEBUG_SOURCE_SHADER_COMPILER => ShaderCompilerSource,
+            gl::DEBUG_SOURCE_THIRD_PARTY => ThirdPartySource,
+            gl::DEBUG_SOURCE_APPLICATION => ApplicationSource,
+            _ => OtherSource,
+        }
+    }
+}
+
+/// What a debug message is about.
+#[deriving(Clone, PartialEq, Show)]
+pub enum DebugType {
+    ErrorType,
+    DeprecatedBehaviorType,
+    UndefinedBehaviorType,
+    PortabilityType,
+    PerformanceType,
+    MarkerType,
+    PushGroupType,
+    PopGroupType,
+    OtherType,
+}
+
+impl DebugType {
+    pub fn from_glenum(type_: GLenum) -> DebugType {
+        match type_ {
+            gl::DEBUG_TYPE_ERROR => ErrorType,
+            gl::DEBUG_TYPE_DEPRECATED_BEHAVIOR => DeprecatedBehaviorType,
+            gl::DEBUG_TYPE_UNDEFINED_BEHAVIOR => UndefinedBehaviorType,
+            gl::DEBUG_TYPE_PORTABILITY => PortabilityType,
+            gl::DEBUG_TYPE_PERFORMANCE => PerformanceType,
+            gl::DEBUG_TYPE_MARKER => MarkerType,
+            gl::DEBUG_TYPE_PUSH_GROUP => PushGroupType,
+            gl::DEBUG_TYPE_POP_GROUP => PopGroupType,
+            _ => OtherType,
+        }
+    }
+}
+
+/// How important a debug message is, from least to most.
+#[deriving(Clone, PartialEq, PartialOrd, Show)]
+pub enum DebugSeverity {
+    NotificationSeverity,
+    LowSeverity,
+    MediumSeverity,
+    HighSeverity,
+}
+
+impl DebugSeverity {
+    pub fn from_glenum(severity: GLenum) -> DebugSeverity {
+        match severity {
+            gl::DEBUG_SEVERITY_HIGH => HighSeverity,
+            gl::DEBUG_SEVERITY_MEDIUM => MediumSeverity,
+            gl::DEBUG_SEVERITY_LOW => LowSeverity,
+            _ => NotificationSeverity,
+        }
+    }
+}
+
+/// A message from the driver (or the application, for debug groups).
+pub struct DebugMessage {
+    pub source: DebugSource,
+    pub type_: DebugType,
+    /// An id chosen by the source, which is the same every time the same
+    /// thing is reported.
+    pub id: GLuint,
+    pub severity: DebugSeverity,
+    pub message: String,
+}
+
+impl fmt::Show for DebugMessage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {} {} ({}): {}", self.severity, self.source, self.type_, self.id,
+               self.message)
+    }
+}
+
+enum Handler<'a> {
+    Callback(|&DebugMessage|: 'a),
+    Log,
+}
+
+/// Everything the GL callback needs, boxed so its address stays put.
+struct State<'a> {
+    /// Borrowed while the handler runs, to drop messages caused by GL calls
+    /// the handler itself makes.
+    handler: RefCell<Handler<'a>>,
+    min_severity: DebugSeverity,
+    ignored: Vec<GLuint>,
+}
+
+/// Forward a message to the log crate, at a level matching its severity.
+fn log_message(msg: &DebugMessage) {
+    match msg.severity {
+        HighSeverity => error!("{}", msg),
+        MediumSeverity => warn!("{}", msg),
+        LowSeverity => info!("{}", msg),
+        NotificationSeverity => debug!("{}", msg),
+    }
+}
+
+/// Aborts the process if dropped while failing, since unwinding out of the
+/// callback into GL is undefined behaviour.
+struct AbortOnFail;
+
+impl Drop for AbortOnFail {
+    fn drop(&mut self) {
+        if task::failing() {
+            unsafe { libc::abort(); }
+        }
+    }
+}
+
+extern "system" fn callback(source: GLenum, type_: GLenum, id: GLuint, severity: GLenum,
+                            _length: GLsizei, message: *const GLchar, user_param: *mut c_void) {
+    if user_param.is_null() {
+        return;
+    }
+    let _guard = AbortOnFail;
+    let state = unsafe { &*(user_param as *const State) };
+    let severity = DebugSeverity::from_glenum(severity);
+    if severity < state.min_severity || state.ignored.contains(&id) {
+        return;
+    }
+
+    let message = unsafe { CString::new(message as *const c_char, false) };
+    let msg = DebugMessage {
+        source: DebugSource::from_glenum(source),
+        type_: DebugType::from_glenum(type_),
+        id: id,
+        severity: severity,
+        message: String::from_utf8_lossy(message.as_bytes_no_nul()).into_string(),
+    };
+    let mut handler = match state.handler.try_borrow_mut() {
+        Some(handler) => handler,
+        None => return, // raised by the handler itself
+    };
+    match *handler {
+        Callback(ref mut f) => (*f)(&msg),
+        Log => log_message(&msg),
+    }
+}
+
+/// The installed debug message callback. Dropping it stops delivering
+/// messages.
+///
+/// GL only has one callback per context, so installing another replaces
+/// this one, and dropping this one then does nothing.
+pub struct DebugOutput<'a> {
+    state: Box<State<'a>>,
+    /// Whether GL_DEBUG_OUTPUT and GL_DEBUG_OUTPUT_SYNCHRONOUS were enabled
+    /// before installing, to be restored on drop.
+    was_enabled: bool,
+    was_synchronous: bool,
+}
+
+#[unsafe_destructor]
+impl<'a> Drop for DebugOutput<'a> {
+    fn drop(&mut self) {
+        let mut current = 0 as *mut c_void;
+        unsafe { gl::GetPointerv(gl::DEBUG_CALLBACK_USER_PARAM, &mut current); }
+        if current as *const c_void != self.user_param() {
+            return;
+        }
+        unsafe { gl::DebugMessageCallback(callback, ptr::null()); }
+        set_enabled(gl::DEBUG_OUTPUT, self.was_enabled);
+        set_enabled(gl::DEBUG_OUTPUT_SYNCHRONOUS, self.was_synchronous);
+    }
+}
+
+fn set_enabled(cap: GLenum, enabled: bool) {
+    if enabled {
+        gl::Enable(cap);
+    } else {
+        gl::Disable(cap);
+    }
+}
+
+/// Deliver debug messages to `handler`. Messages are delivered
+/// synchronously, on the thread that made the GL call which caused them, so
+/// a breakpoint in the handler shows where they came from.
+///
+/// The handler is called from inside GL, which can't be unwound through, so
+/// if it fails the process is aborted. Messages caused by GL calls the
+/// handler makes are dropped, rather than calling it recursively.
+pub fn install<'a>(handler: |&DebugMessage|: 'a) -> Result<DebugOutput<'a>, Error> {
+    install_handler(Callback(handler))
+}
+
+/// As `install`, but logging each message with the log crate: high
+/// severity messages are errors, medium are warnings, low are info, and
+/// notifications are debug.
+pub fn install_log() -> Result<DebugOutput<'static>, Error> {
+    install_handler(Log)
+}
+
+fn install_handler<'a>(handler: Handler<'a>) -> Result<DebugOutput<'a>, Error> {
+    if !is_supported() {
+        return Err(MissingExtension("GL_KHR_debug"));
+    }
+    let output = DebugOutput {
+        state: box State {
+            handler: RefCell::new(handler),
+            min_severity: NotificationSeverity,
+            ignored: Vec::new(),
+        },
+        was_enabled: gl::IsEnabled(gl::DEBUG_OUTPUT) == gl::TRUE,
+        was_synchronous: gl::IsEnabled(gl::DEBUG_OUTPUT_SYNCHRONOUS) == gl::TRUE,
+    };
+    gl::Enable(gl::DEBUG_OUTPUT);
+    gl::Enable(gl::DEBUG_OUTPUT_SYNCHRONOUS);
+    unsafe { gl::DebugMessageCallback(callback, output.user_param()); }
+    try!(error::check("debug::install"));
+    Ok(output)
+}
+
+impl<'a> DebugOutput<'a> {
+    /// The pointer GL passes back to `callback`.
+    fn user_param(&self) -> *const c_void {
+        &*self.state as *const State as *const c_void
+    }
+
+    /// Drop messages less severe than `severity`.
+    pub fn min_severity(&mut self, severity: DebugSeverity) {
+        self.state.min_severity = severity;
+    }
+
+    /// Drop messages with the id `id`, for noise that can't be fixed.
+    pub fn ignore(&mut self, id: GLuint) {
+        if !self.state.ignored.contains(&id) {
+            self.state.ignored.push(id);
+        }
+    }
+}
+
+/// Start a debug group named `message`, which debuggers such as apitrace
+/// and RenderDoc show around the calls made until the matching `pop_group`.
+/// `id` is passed along to the debug callback.
+pub fn push_group(id: GLuint, message: &str) {
+    unsafe {
+        gl::PushDebugGroup(gl::DEBUG_SOURCE_APPLICATION, id, message.len() as GLsizei,
+                           message.as_ptr() as *const GLchar);
+    }
+    error::check_or_fail("debug::push_group");
+}
+
+/// End the innermost debug group.
+pub fn pop_group() {
+    gl::PopDebugGroup();
+    error::check_or_fail("debug::pop_group");
+}
+
+/// Give the object `name` of kind `identifier` (such as `gl::BUFFER`) a
+/// label, which shows up in debug messages and debuggers. The object must
+/// have been bound at least once.
+pub fn object_label(identifier: GLenum, name: GLuint, label: &str) {
+    unsafe {
+        gl::ObjectLabel(identifier, name, label.len() as GLsizei,
+                        label.as_ptr() as *const GLchar);
+    }
+    error::check_or_fail("debug::object_label");
+}
@@ -0,0 +1,148 @@
+//! The error type shared by every fallible hgl function, and optional
+//! checking of glGetError.
+
+use gl;
+use std::fmt;
+use std::io::IoError;
+use std::sync::atomic::{AtomicBool, INIT_ATOMIC_BOOL, SeqCst};
+use gl::types::GLenum;
+
+use program::{ShaderError, ValidationError};
+
+/// Why an hgl operation failed.
+#[deriving(Clone)]
+pub enum Error {
+    /// Reading a file (such as a shader or an include) failed.
+    Io(IoError),
+    /// A shader failed to compile.
+    Compile(ShaderError),
+    /// A program failed to link.
+    Link(ShaderError),
+    /// A program or pipeline can't execute with the current GL state.
+    Validation(ValidationError),
+    /// An argument was missing or made no sense, such as a 2D image without
+    /// a height, a vertex attribute the program doesn't have, or shader
+    /// source with a malformed or cyclic `#include`.
+    InvalidArgument(String),
+    /// GL reported an error after a call to the named hgl function. Only
+    /// reported when error checking is on; see `set_check_errors`.
+    Gl(GlError, &'static str),
+    /// The context lacks the version or extension needed, which is named.
+    MissingExtension(&'static str),
+}
+
+impl fmt::Show for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Io(ref e) => write!(f, "{}", e),
+            Compile(ref e) => write!(f, "{}", e),
+            Link(ref e) => write!(f, "{}", e),
+            Validation(ref e) => write!(f, "{}", e),
+            InvalidArgument(ref msg) => write!(f, "invalid argument: {}", msg),
+            Gl(e, func) => write!(f, "{} in {}", e, func),
+            MissingExtension(ext) => write!(f, "{} is not supported by this GL context", ext),
+        }
+    }
+}
+
+/// An error recorded by GL, as returned by glGetError.
+#[deriving(Clone, PartialEq)]
+pub enum GlError {
+    InvalidEnum,
+    InvalidValue,
+    InvalidOperation,
+    InvalidFramebufferOperation,
+    OutOfMemory,
+    StackUnderflow,
+    StackOverflow,
+    /// An error code this crate doesn't know about.
+    UnknownGlError(GLenum),
+}
+
+impl GlError {
+    pub fn from_glenum(code: GLenum) -> GlError {
+        match code {
+            gl::INVALID_ENUM => InvalidEnum,
+            gl::INVALID_VALUE => InvalidValue,
+            gl::INVALID_OPERATION => InvalidOperation,
+            gl::INVALID_FRAMEBUFFER_OPERATION => InvalidFramebufferOperation,
+            gl::OUT_OF_MEMORY => OutOfMemory,
+            gl::STACK_UNDERFLOW => StackUnderflow,
+            gl::STACK_OVERFLOW => StackOverflow,
+            code => UnknownGlError(code),
+        }
+    }
+
+    pub fn to_glenum(&self) -> GLenum {
+        match *self {
+            InvalidEnum => gl::INVALID_ENUM,
+            InvalidValue => gl::INVALID_VALUE,
+            InvalidOperation => gl::INVALID_OPERATION,
+            InvalidFramebufferOperation => gl::INVALID_FRAMEBUFFER_OPERATION,
+            OutOfMemory => gl::OUT_OF_MEMORY,
+            StackUnderflow => gl::STACK_UNDERFLOW,
+            StackOverflow => gl::STACK_OVERFLOW,
+            UnknownGlError(code) => code,
+        }
+    }
+}
+
+impl fmt::Show for GlError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            InvalidEnum => write!(f, "GL_INVALID_ENUM"),
+            InvalidValue => write!(f, "GL_INVALID_VALUE"),
+            InvalidOperation => write!(f, "GL_INVALID_OPERATION"),
+            InvalidFramebufferOperation => write!(f, "GL_INVALID_FRAMEBUFFER_OPERATION"),
+            OutOfMemory => write!(f, "GL_OUT_OF_MEMORY"),
+            StackUnderflow => write!(f, "GL_STACK_UNDERFLOW"),
+            StackOverflow => write!(f, "GL_STACK_OVERFLOW"),
+            UnknownGlError(code) => write!(f, "GL error 0x{:04x}", code),
+        }
+    }
+}
+
+static CHECK_ERRORS: AtomicBool = INIT_ATOMIC_BOOL;
+
+/// Turn error checking on or off. When on, every hgl function calls
+/// glGetError after its GL calls. Functions that return a `Result` return
+/// the error as `Gl`; the rest fail the task with it. Errors left behind by
+/// GL calls made outside of hgl are blamed on the next hgl function called.
+/// Like draw validation, this is slow and meant for debugging.
+pub fn set_check_errors(enabled: bool) {
+    CHECK_ERRORS.store(enabled, SeqCst);
+}
+
+/// Whether error checking is on.
+pub fn check_errors() -> bool {
+    CHECK_ERRORS.load(SeqCst)
+}
+
+/// If error checking is on, return the first error GL has recorded,
+/// blaming `func`, and clear the rest.
+pub fn check(func: &'static str) -> Result<(), Error> {
+    if !check_errors() {
+        return Ok(());
+    }
+    let first = gl::GetError();
+    if first == gl::NO_ERROR {
+        return Ok(());
+    }
+    // GL can record one error per flag; bounded in case a broken context
+    // never stops reporting them.
+    for _ in range(0u, 16) {
+        if gl::GetError() == gl::NO_ERROR {
+            break;
+        }
+    }
+    Err(Gl(GlError::from_glenum(first), func))
+}
+
+/// As `check`, but failing the task, for functions that don't return a
+/// `Result`.
+pub fn check_or_fail(func: &'static str) {
+    match check(func) {
+        Ok(()) => {},
+        Err(e) => fail!("{}", e)
+    }
+}
@@ -0,0 +1,119 @@
+//! Transform feedback: capturing the output of vertex processing into
+//! buffers.
+//!
+//! Pick the varyings to capture with `ProgramBuilder::feedback_varyings`,
+//! attach buffers to a `TransformFeedback`, and draw between `begin` and
+//! `end`.
+
+use gl;
+use gl::types::{GLuint, GLenum};
+
+use buffer::Buffer;
+use context;
+use error;
+use error::{Error, MissingExtension};
+use {Primitive, Points, Lines, LineStrip, LineLoop, Triangles, TriangleStrip, TriangleFan};
+
+/// How captured varyings are laid out in buffers.
+pub enum FeedbackMode {
+    /// All varyings are written to the buffer at index 0, one vertex after
+    /// another.
+    Interleaved,
+    /// Each varying is written to the buffer at its own index.
+    Separate,
+}
+
+impl FeedbackMode {
+    pub fn to_glenum(&self) -> GLenum {
+        match *self {
+            Interleaved => gl::INTERLEAVED_ATTRIBS,
+            Separate => gl::SEPARATE_ATTRIBS,
+        }
+    }
+}
+
+/// The mode glBeginTransformFeedback needs for drawing `primitive`. Strips,
+/// loops and fans are captured as independent primitives.
+fn capture_mode(primitive: Primitive) -> GLenum {
+    match primitive {
+        Points => gl::POINTS,
+        Lines | LineStrip | LineLoop => gl::LINES,
+        Triangles | TriangleStrip | TriangleFan => gl::TRIANGLES,
+    }
+}
+
+/// A transform feedback object, which remembers its buffer bindings and
+/// how much was captured. Requires GL 4.0 or ARB_transform_feedback2.
+pub struct TransformFeedback {
+    pub name: GLuint
+}
+
+impl Drop for TransformFeedback {
+    fn drop(&mut self) {
+        unsafe { gl::DeleteTransformFeedbacks(1, &self.name); }
+    }
+}
+
+impl TransformFeedback {
+    /// Create a new transform feedback object, without binding it.
+    pub fn new() -> Result<TransformFeedback, Error> {
+        if !context::supports_version(4, 0) && !context::has_extension("GL_ARB_transform_feedback2") {
+            return Err(MissingExtension("GL_ARB_transform_feedback2"));
+        }
+        let mut tf = 0;
+        unsafe { gl::GenTransformFeedbacks(1, &mut tf); }
+        let tf = TransformFeedback { name: tf };
+        try!(error::check("TransformFeedback::new"));
+        Ok(tf)
+    }
+
+    pub fn bind(&self) {
+        gl::BindTransformFeedback(gl::TRANSFORM_FEEDBACK, self.name);
+        error::check_or_fail("TransformFeedback::bind");
+    }
+
+    /// Capture into `buffer` at `index`. With `Interleaved` only index 0 is
+    /// used; with `Separate` the index is the position of the varying in the
+    /// list given to the program. The buffer must already be large enough.
+    ///
+    /// Any kind of buffer works; pass a `Vbo` or `TypedBuffer` as `&*vbo`.
+    pub fn bind_buffer(&self, index: GLuint, buffer: &Buffer) {
+        self.bind();
+        gl::BindBufferBase(gl::TRANSFORM_FEEDBACK_BUFFER, index, buffer.name);
+        error::check_or_fail("TransformFeedback::bind_buffer");
+    }
+
+    /// Start capturing. Draw calls until `end` must use `primitive`, or a
+    /// strip/loop/fan of the same kind.
+    pub fn begin(&self, primitive: Primitive) {
+        self.bind();
+        gl::BeginTransformFeedback(capture_mode(primitive));
+        error::check_or_fail("TransformFeedback::begin");
+    }
+
+    /// Stop capturing.
+    pub fn end(&self) {
+        gl::EndTransformFeedback();
+        error::check_or_fail("TransformFeedback::end");
+    }
+
+    /// Temporarily stop capturing, so other draws can happen.
+    pub fn pause(&self) {
+        gl::PauseTransformFeedback();
+        error::check_or_fail("TransformFeedback::pause");
+    }
+
+    /// Continue capturing after `pause`.
+    pub fn resume(&self) {
+        gl::ResumeTransformFeedback();
+        error::check_or_fail("TransformFeedback::resume");
+    }
+
+    /// Draw the vertices captured between the last `begin` and `end`,
+    /// without needing to query how many there were. The captured buffers
+    /// must be set up as vertex attributes in the bound VAO.
+    pub fn draw(&self, primitive: Primitive) {
+        gl::DrawTransformFeedback(primitive.to_glenum(), self.name);
+        error::check_or_fail("TransformFeedback::draw");
+    }
+}
@@ -0,0 +1,200 @@
+//! Parsing shader and program info logs into diagnostics.
+//!
+//! Drivers don't agree on a format. The ones understood here are:
+//!
+//! * Mesa: `0:12(5): error: message`
+//! * NVIDIA: `0(12) : error C0000: message`
+//! * AMD: `ERROR: 0:12: message`
+//!
+//! Any other line is kept as a diagnostic without a location.
+
+use std::fmt;
+
+#[deriving(Clone, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+}
+
+impl fmt::Show for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", match *self {
+            Error => "error",
+            Warning => "warning",
+            Note => "note",
+        })
+    }
+}
+
+/// A single message from an info log.
+#[deriving(Clone)]
+pub struct Diagnostic {
+    /// The source string number. This is 0 unless the source used `#line`
+    /// to change it.
+    pub source: Option<uint>,
+    /// The (1-based) line number within the source string.
+    pub line: Option<uint>,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl fmt::Show for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self.source, self.line) {
+            (Some(source), Some(line)) => try!(write!(f, "{}:{}: ", source, line)),
+            (None, Some(line)) => try!(write!(f, "{}: ", line)),
+            _ => {}
+        }
+        write!(f, "{}: {}", self.severity, self.message)
+    }
+}
+
+macro_rules! opt(
+    ($e:expr) => (match $e { Some(x) => x, None => return None })
+)
+
+/// Parse an info log, one diagnostic per non-empty line.
+pub fn parse(log: &str) -> Vec<Diagnostic> {
+    log.lines()
+       .map(|l| l.trim())
+       .filter(|l| !l.is_empty())
+       .map(|l| {
+           parse_mesa(l)
+               .or_else(|| parse_nvidia(l))
+               .or_else(|| parse_amd(l))
+               .unwrap_or_else(|| unlocated(l))
+       })
+       .collect()
+}
+
+fn diagnostic(source: uint, line: uint, severity: Severity, message: &str) -> Diagnostic {
+    Diagnostic {
+        source: Some(source),
+        line: Some(line),
+        severity: severity,
+        message: message.trim().to_string(),
+    }
+}
+
+fn unlocated(l: &str) -> Diagnostic {
+    let lower: String = l.chars().map(|c| c.to_lowercase()).collect();
+    Diagnostic {
+        source: None,
+        line: None,
+        severity: if lower.as_slice().contains("warning") { Warning } else { Error },
+        message: l.to_string(),
+    }
+}
+
+fn severity(s: &str) -> Option<Severity> {
+    match s.trim() {
+        "error" | "ERROR" | "fatal error" => Some(Error),
+        "warning" | "WARNING" => Some(Warning),
+        "note" | "info" | "INFO" => Some(Note),
+        _ => None
+    }
+}
+
+/// Split a leading decimal number off `s`.
+fn number<'a>(s: &'a str) -> Option<(uint, &'a str)> {
+    let end = s.find(|c: char| !c.is_digit()).unwrap_or(s.len());
+    from_str(s.slice_to(end)).map(|n| (n, s.slice_from(end)))
+}
+
+/// Strip `prefix` off `s`, if `s` starts with it.
+fn expect<'a>(s: &'a str, prefix: &str) -> Option<&'a str> {
+    if s.starts_with(prefix) { Some(s.slice_from(prefix.len())) } else { None }
+}
+
+/// `0:12(5): error: message`
+fn parse_mesa(l: &str) -> Option<Diagnostic> {
+    let (source, rest) = opt!(number(l));
+    let (line, rest) = opt!(number(opt!(expect(rest, ":"))));
+    let rest = opt!(expect(rest, "("));
+    let rest = opt!(expect(rest.slice_from(opt!(rest.find(')'))), "):"));
+    let colon = opt!(rest.find(':'));
+    let sev = opt!(severity(rest.slice_to(colon)));
+    Some(diagnostic(source, line, sev, rest.slice_from(colon + 1)))
+}
+
+/// `0(12) : error C0000: message`
+fn parse_nvidia(l: &str) -> Option<Diagnostic> {
+    let (source, rest) = opt!(number(l));
+    let (line, rest) = opt!(number(opt!(expect(rest, "("))));
+    let rest = opt!(expect(rest, ")")).trim_left();
+    let rest = opt!(expect(rest, ":")).trim_left();
+    let colon = opt!(rest.find(':'));
+    // the severity is followed by the error code
+    let sev = opt!(severity(opt!(rest.slice_to(colon).words().next())));
+    Some(diagnostic(source, line, sev, rest.slice_from(colon + 1)))
+}
+
+/// `ERROR: 0:12: message`
+fn parse_amd(l: &str) -> Option<Diagnostic> {
+    let colon = opt!(l.find(':'));
+    let sev = opt!(severity(l.slice_to(colon)));
+    let (source, rest) = opt!(number(l.slice_from(colon + 1).trim_left()));
+    let (line, rest) = opt!(number(opt!(expect(rest, ":"))));
+    let rest = opt!(expect(rest, ":"));
+    Some(diagnostic(source, line, sev, rest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse, Error, Warning};
+
+    #[test]
+    fn mesa() {
+        let d = parse("0:12(5): error: `foo' undeclared\n");
+        let d = d.as_slice();
+        assert_eq!(d.len(), 1);
+        assert_eq!(d[0].source, Some(0));
+        assert_eq!(d[0].line, Some(12));
+        assert_eq!(d[0].severity, Error);
+        assert_eq!(d[0].message.as_slice(), "`foo' undeclared");
+    }
+
+    #[test]
+    fn nvidia() {
+        let d = parse("0(12) : error C0000: syntax error, unexpected '}'");
+        let d = d.as_slice();
+        assert_eq!(d.len(), 1);
+        assert_eq!(d[0].source, Some(0));
+        assert_eq!(d[0].line, Some(12));
+        assert_eq!(d[0].severity, Error);
+        assert_eq!(d[0].message.as_slice(), "syntax error, unexpected '}'");
+    }
+
+    #[test]
+    fn amd() {
+        let d = parse("ERROR: 0:12: 'x' : undeclared identifier");
+        let d = d.as_slice();
+        assert_eq!(d.len(), 1);
+        assert_eq!(d[0].source, Some(0));
+        assert_eq!(d[0].line, Some(12));
+        assert_eq!(d[0].severity, Error);
+        assert_eq!(d[0].message.as_slice(), "'x' : undeclared identifier");
+    }
+
+    #[test]
+    fn unlocated_warning() {
+        let d = parse("\nwarning: extension GL_ARB_foo is unsupported\n\n");
+        let d = d.as_slice();
+        assert_eq!(d.len(), 1);
+        assert_eq!(d[0].source, None);
+        assert_eq!(d[0].line, None);
+        assert_eq!(d[0].severity, Warning);
+        assert_eq!(d[0].message.as_slice(), "warning: extension GL_ARB_foo is unsupported");
+    }
+
+    #[test]
+    fn one_per_line() {
+        let d = parse("0:1(1): warning: unused\n0:2(3): error: bad");
+        let d = d.as_slice();
+        assert_eq!(d.len(), 2);
+        assert_eq!(d[0].severity, Warning);
+        assert_eq!(d[1].line, Some(2));
+        assert_eq!(d[1].severity, Error);
+    }
+}
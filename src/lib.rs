@@ -9,6 +9,8 @@
 #![comment = "Helper utilities for working with OpenGL"]
 #![deprecated = "Use gfx-rs instead"]
 
+#![feature(macro_rules, phase, unsafe_destructor)]
+
 //! hgl-rs - helpers for working with OpenGL.
 //!
 //! hgl assumes GL 3.1 with GLSL 140. There are reexports for most types, but
@@ -18,28 +20,59 @@
 //! but the other methods frequently bind themselves too! Be careful what you
 //! call if you expect something to be bound to stay bound. They do not
 //! restore the current binding before they return.
+//!
+//! Everything that can fail returns a `Result` with `hgl::Error`.
 
 
 extern crate gl;
 extern crate libc;
+#[phase(plugin, link)] extern crate log;
 
-pub use program::{VertexShader, FragmentShader, Shader, Program};
-pub use buffer::{Vbo, Ebo, StaticDraw};
+pub use program::{VertexShader, FragmentShader, GeometryShader, TessControlShader,
+                  TessEvaluationShader, ComputeShader, Shader, ShaderError, Program,
+                  ProgramBuilder, Preprocessor};
+pub use buffer::{Buffer, TypedBuffer, Vbo, Ebo, UniformBuffer, StaticDraw};
+pub use cache::ProgramCache;
+pub use error::{Error, GlError};
+pub use feedback::TransformFeedback;
+pub use pipeline::ProgramPipeline;
 pub use query::Query;
+pub use reload::ReloadableProgram;
+pub use stream::StreamBuffer;
 pub use vao::Vao;
 pub use texture::{ImageInfo, Texture};
+pub use uniform::{Uniform, Sampler};
 
 use gl::types::{GLuint, GLenum};
 
+pub mod context;
+pub mod debug;
+pub mod error;
+pub mod infolog;
 pub mod program;
 pub mod buffer;
+pub mod cache;
+pub mod feedback;
+pub mod pipeline;
 pub mod query;
+pub mod std140;
+pub mod stream;
+pub mod reload;
 pub mod vao;
 pub mod texture;
+pub mod uniform;
+
+/// Lets the tests use exported macros, which name their items through
+/// `::hgl`.
+#[cfg(test)]
+mod hgl {
+    pub use std140;
+}
 
 /// A simple wrapper for glPrimitiveRestartIndex.
 pub fn restart_index(index: GLuint) {
     gl::PrimitiveRestartIndex(index);
+    error::check_or_fail("restart_index");
 }
 
 // move this into container after #12661 and #12660 are fixed
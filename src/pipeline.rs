@@ -0,0 +1,95 @@
+//! Program pipelines, for mixing stages from separately linked programs.
+
+use gl;
+use gl::types::GLuint;
+
+use context;
+use error;
+use error::{Error, InvalidArgument, MissingExtension};
+use program::{Program, ShaderType};
+use uniform::Uniform;
+
+/// Whether the current context supports program pipelines.
+pub fn is_supported() -> bool {
+    context::supports_version(4, 1) || context::has_extension("GL_ARB_separate_shader_objects")
+}
+
+/// A program pipeline object. Each stage can come from a different program,
+/// as long as each was linked with `ProgramBuilder::separable(true)`.
+/// Requires GL 4.1 or ARB_separate_shader_objects.
+pub struct ProgramPipeline<'a> {
+    pub name: GLuint,
+    stages: Vec<(ShaderType, &'a Program)>,
+}
+
+#[unsafe_destructor]
+impl<'a> Drop for ProgramPipeline<'a> {
+    fn drop(&mut self) {
+        unsafe { gl::DeleteProgramPipelines(1, &self.name); }
+    }
+}
+
+impl<'a> ProgramPipeline<'a> {
+    /// Create a new pipeline with no stages, without binding it.
+    pub fn new() -> Result<ProgramPipeline<'a>, Error> {
+        if !is_supported() {
+            return Err(MissingExtension("GL_ARB_separate_shader_objects"));
+        }
+        let mut pipeline = 0;
+        unsafe { gl::GenProgramPipelines(1, &mut pipeline); }
+        let pipeline = ProgramPipeline { name: pipeline, stages: Vec::new() };
+        try!(error::check("ProgramPipeline::new"));
+        Ok(pipeline)
+    }
+
+    /// Bind this pipeline. This unbinds any program bound with
+    /// `Program::bind`, which would otherwise take precedence.
+    pub fn bind(&self) {
+        gl::UseProgram(0);
+        gl::BindProgramPipeline(self.name);
+        error::check_or_fail("ProgramPipeline::bind");
+    }
+
+    /// Use `program` for each of `stages`, replacing whatever program they
+    /// used before. Fails if `program` isn't separable.
+    pub fn use_stages(&mut self, stages: &[ShaderType],
+                      program: &'a Program) -> Result<(), Error> {
+        if !program.is_separable() {
+            return Err(InvalidArgument("program is not separable".to_string()));
+        }
+        let bits = stages.iter().fold(0, |bits, s| bits | s.to_stage_bit());
+        gl::UseProgramStages(self.name, bits, program.name);
+        try!(error::check("ProgramPipeline::use_stages"));
+
+        self.stages.retain(|&(s, _)| !stages.contains(&s));
+        for &stage in stages.iter() {
+            self.stages.push((stage, program));
+        }
+        Ok(())
+    }
+
+    /// The program used for `stage`, if any.
+    pub fn stage_program(&self, stage: ShaderType) -> Option<&'a Program> {
+        self.stages.iter().find(|&&(s, _)| s == stage).map(|&(_, p)| p)
+    }
+
+    /// Set the uniform `name` of the program used for `stage` to `value`.
+    /// This binds the pipeline. Fails if no program is used for `stage`, or
+    /// it has no active uniform called `name`.
+    pub fn set_uniform<T: Uniform>(&self, stage: ShaderType, name: &str,
+                                   value: T) -> Result<(), Error> {
+        let program = match self.stage_program(stage) {
+            Some(program) => program,
+            None => return Err(InvalidArgument(format!("no program is used for {}", stage)))
+        };
+        let location = program.uniform(name);
+        if location < 0 {
+            return Err(InvalidArgument(format!("no active uniform named {} in {}", name, stage)));
+        }
+        self.bind();
+        // glUniform* goes to the active program of the bound pipeline
+        gl::ActiveShaderProgram(self.name, program.name);
+        value.set(location);
+        error::check("ProgramPipeline::set_uniform")
+    }
+}
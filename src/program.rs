@@ -1,13 +1,39 @@
 //! Dealing with Programs and Shaders
 
 use gl;
-use std::io::{File, IoResult};
-use gl::types::{GLint, GLuint, GLenum, GLsizei, GLchar};
+use libc::c_void;
+use std::fmt;
+use std::io::File;
+use std::collections::HashMap;
+use std::c_str::CString;
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicBool, INIT_ATOMIC_BOOL, SeqCst};
+use gl::types::{GLint, GLuint, GLenum, GLsizei, GLchar, GLbitfield};
+
+use context;
+use debug;
+use error;
+use error::{Error, Io, Compile, Link, Validation, InvalidArgument, MissingExtension};
+use feedback::FeedbackMode;
+use infolog;
+use pipeline;
+use infolog::Diagnostic;
+use uniform::Uniform;
 
 /// Shader types
+#[deriving(Clone, PartialEq, Show)]
 pub enum ShaderType {
     VertexShader,
     FragmentShader,
+    /// Requires GL 3.2
+    GeometryShader,
+    /// Requires GL 4.0 or ARB_tessellation_shader
+    TessControlShader,
+    /// Requires GL 4.0 or ARB_tessellation_shader
+    TessEvaluationShader,
+    /// Requires GL 4.3 or ARB_compute_shader. Compute shaders can only be
+    /// linked with other compute shaders.
+    ComputeShader,
 }
 
 impl ShaderType {
@@ -16,57 +42,505 @@ impl ShaderType {
         match *self {
             VertexShader => gl::VERTEX_SHADER,
             FragmentShader => gl::FRAGMENT_SHADER,
+            GeometryShader => gl::GEOMETRY_SHADER,
+            TessControlShader => gl::TESS_CONTROL_SHADER,
+            TessEvaluationShader => gl::TESS_EVALUATION_SHADER,
+            ComputeShader => gl::COMPUTE_SHADER,
+        }
+    }
+
+    /// The bit for this stage in glUseProgramStages.
+    pub fn to_stage_bit(&self) -> GLbitfield {
+        match *self {
+            VertexShader => gl::VERTEX_SHADER_BIT,
+            FragmentShader => gl::FRAGMENT_SHADER_BIT,
+            GeometryShader => gl::GEOMETRY_SHADER_BIT,
+            TessControlShader => gl::TESS_CONTROL_SHADER_BIT,
+            TessEvaluationShader => gl::TESS_EVALUATION_SHADER_BIT,
+            ComputeShader => gl::COMPUTE_SHADER_BIT,
+        }
+    }
+
+    /// Whether the current context can compile shaders of this type.
+    pub fn is_supported(&self) -> bool {
+        match *self {
+            VertexShader | FragmentShader => true,
+            GeometryShader => context::supports_version(3, 2),
+            TessControlShader | TessEvaluationShader => {
+                context::supports_version(4, 0)
+                    || context::has_extension("GL_ARB_tessellation_shader")
+            },
+            ComputeShader => {
+                context::supports_version(4, 3)
+                    || context::has_extension("GL_ARB_compute_shader")
+            },
+        }
+    }
+
+    /// What to ask for when this type isn't supported.
+    fn required_extension(&self) -> &'static str {
+        match *self {
+            VertexShader | FragmentShader => "GL 2.0",
+            GeometryShader => "GL 3.2",
+            TessControlShader | TessEvaluationShader => "GL_ARB_tessellation_shader",
+            ComputeShader => "GL_ARB_compute_shader",
+        }
+    }
+}
+
+/// The type of an active uniform or attribute, as reported by
+/// glGetActiveUniform and glGetActiveAttrib.
+#[deriving(PartialEq, Show)]
+pub enum GlslType {
+    Float,
+    FloatVec2,
+    FloatVec3,
+    FloatVec4,
+    Double,
+    DoubleVec2,
+    DoubleVec3,
+    DoubleVec4,
+    Int,
+    IntVec2,
+    IntVec3,
+    IntVec4,
+    UnsignedInt,
+    UnsignedIntVec2,
+    UnsignedIntVec3,
+    UnsignedIntVec4,
+    Bool,
+    BoolVec2,
+    BoolVec3,
+    BoolVec4,
+    FloatMat2,
+    FloatMat3,
+    FloatMat4,
+    FloatMat2x3,
+    FloatMat2x4,
+    FloatMat3x2,
+    FloatMat3x4,
+    FloatMat4x2,
+    FloatMat4x3,
+    Sampler1D,
+    Sampler2D,
+    Sampler3D,
+    SamplerCube,
+    Sampler1DArray,
+    Sampler2DArray,
+    Sampler2DRect,
+    SamplerBuffer,
+    Sampler2DMultisample,
+    Sampler1DShadow,
+    Sampler2DShadow,
+    SamplerCubeShadow,
+    Sampler1DArrayShadow,
+    Sampler2DArrayShadow,
+    Sampler2DRectShadow,
+    IntSampler1D,
+    IntSampler2D,
+    IntSampler3D,
+    IntSamplerCube,
+    IntSampler1DArray,
+    IntSampler2DArray,
+    IntSampler2DRect,
+    IntSamplerBuffer,
+    IntSampler2DMultisample,
+    UnsignedIntSampler1D,
+    UnsignedIntSampler2D,
+    UnsignedIntSampler3D,
+    UnsignedIntSamplerCube,
+    UnsignedIntSampler1DArray,
+    UnsignedIntSampler2DArray,
+    UnsignedIntSampler2DRect,
+    UnsignedIntSamplerBuffer,
+    UnsignedIntSampler2DMultisample,
+    /// A type hgl doesn't know about (eg, from an extension)
+    UnknownType(GLenum),
+}
+
+impl GlslType {
+    /// Convert a GL type enum into a GlslType
+    pub fn from_glenum(ty: GLenum) -> GlslType {
+        match ty {
+            gl::FLOAT => Float,
+            gl::FLOAT_VEC2 => FloatVec2,
+            gl::FLOAT_VEC3 => FloatVec3,
+            gl::FLOAT_VEC4 => FloatVec4,
+            gl::DOUBLE => Double,
+            gl::DOUBLE_VEC2 => DoubleVec2,
+            gl::DOUBLE_VEC3 => DoubleVec3,
+            gl::DOUBLE_VEC4 => DoubleVec4,
+            gl::INT => Int,
+            gl::INT_VEC2 => IntVec2,
+            gl::INT_VEC3 => IntVec3,
+            gl::INT_VEC4 => IntVec4,
+            gl::UNSIGNED_INT => UnsignedInt,
+            gl::UNSIGNED_INT_VEC2 => UnsignedIntVec2,
+            gl::UNSIGNED_INT_VEC3 => UnsignedIntVec3,
+            gl::UNSIGNED_INT_VEC4 => UnsignedIntVec4,
+            gl::BOOL => Bool,
+            gl::BOOL_VEC2 => BoolVec2,
+            gl::BOOL_VEC3 => BoolVec3,
+            gl::BOOL_VEC4 => BoolVec4,
+            gl::FLOAT_MAT2 => FloatMat2,
+            gl::FLOAT_MAT3 => FloatMat3,
+            gl::FLOAT_MAT4 => FloatMat4,
+            gl::FLOAT_MAT2x3 => FloatMat2x3,
+            gl::FLOAT_MAT2x4 => FloatMat2x4,
+            gl::FLOAT_MAT3x2 => FloatMat3x2,
+            gl::FLOAT_MAT3x4 => FloatMat3x4,
+            gl::FLOAT_MAT4x2 => FloatMat4x2,
+            gl::FLOAT_MAT4x3 => FloatMat4x3,
+            gl::SAMPLER_1D => Sampler1D,
+            gl::SAMPLER_2D => Sampler2D,
+            gl::SAMPLER_3D => Sampler3D,
+            gl::SAMPLER_CUBE => SamplerCube,
+            gl::SAMPLER_1D_ARRAY => Sampler1DArray,
+            gl::SAMPLER_2D_ARRAY => Sampler2DArray,
+            gl::SAMPLER_2D_RECT => Sampler2DRect,
+            gl::SAMPLER_BUFFER => SamplerBuffer,
+            gl::SAMPLER_2D_MULTISAMPLE => Sampler2DMultisample,
+            gl::SAMPLER_1D_SHADOW => Sampler1DShadow,
+            gl::SAMPLER_2D_SHADOW => Sampler2DShadow,
+            gl::SAMPLER_CUBE_SHADOW => SamplerCubeShadow,
+            gl::SAMPLER_1D_ARRAY_SHADOW => Sampler1DArrayShadow,
+            gl::SAMPLER_2D_ARRAY_SHADOW => Sampler2DArrayShadow,
+            gl::SAMPLER_2D_RECT_SHADOW => Sampler2DRectShadow,
+            gl::INT_SAMPLER_1D => IntSampler1D,
+            gl::INT_SAMPLER_2D => IntSampler2D,
+            gl::INT_SAMPLER_3D => IntSampler3D,
+            gl::INT_SAMPLER_CUBE => IntSamplerCube,
+            gl::INT_SAMPLER_1D_ARRAY => IntSampler1DArray,
+            gl::INT_SAMPLER_2D_ARRAY => IntSampler2DArray,
+            gl::INT_SAMPLER_2D_RECT => IntSampler2DRect,
+            gl::INT_SAMPLER_BUFFER => IntSamplerBuffer,
+            gl::INT_SAMPLER_2D_MULTISAMPLE => IntSampler2DMultisample,
+            gl::UNSIGNED_INT_SAMPLER_1D => UnsignedIntSampler1D,
+            gl::UNSIGNED_INT_SAMPLER_2D => UnsignedIntSampler2D,
+            gl::UNSIGNED_INT_SAMPLER_3D => UnsignedIntSampler3D,
+            gl::UNSIGNED_INT_SAMPLER_CUBE => UnsignedIntSamplerCube,
+            gl::UNSIGNED_INT_SAMPLER_1D_ARRAY => UnsignedIntSampler1DArray,
+            gl::UNSIGNED_INT_SAMPLER_2D_ARRAY => UnsignedIntSampler2DArray,
+            gl::UNSIGNED_INT_SAMPLER_2D_RECT => UnsignedIntSampler2DRect,
+            gl::UNSIGNED_INT_SAMPLER_BUFFER => UnsignedIntSamplerBuffer,
+            gl::UNSIGNED_INT_SAMPLER_2D_MULTISAMPLE => UnsignedIntSampler2DMultisample,
+            other => UnknownType(other),
         }
     }
+
+    /// Convert a GlslType into its corresponding GL value
+    pub fn to_glenum(&self) -> GLenum {
+        match *self {
+            Float => gl::FLOAT,
+            FloatVec2 => gl::FLOAT_VEC2,
+            FloatVec3 => gl::FLOAT_VEC3,
+            FloatVec4 => gl::FLOAT_VEC4,
+            Double => gl::DOUBLE,
+            DoubleVec2 => gl::DOUBLE_VEC2,
+            DoubleVec3 => gl::DOUBLE_VEC3,
+            DoubleVec4 => gl::DOUBLE_VEC4,
+            Int => gl::INT,
+            IntVec2 => gl::INT_VEC2,
+            IntVec3 => gl::INT_VEC3,
+            IntVec4 => gl::INT_VEC4,
+            UnsignedInt => gl::UNSIGNED_INT,
+            UnsignedIntVec2 => gl::UNSIGNED_INT_VEC2,
+            UnsignedIntVec3 => gl::UNSIGNED_INT_VEC3,
+            UnsignedIntVec4 => gl::UNSIGNED_INT_VEC4,
+            Bool => gl::BOOL,
+            BoolVec2 => gl::BOOL_VEC2,
+            BoolVec3 => gl::BOOL_VEC3,
+            BoolVec4 => gl::BOOL_VEC4,
+            FloatMat2 => gl::FLOAT_MAT2,
+            FloatMat3 => gl::FLOAT_MAT3,
+            FloatMat4 => gl::FLOAT_MAT4,
+            FloatMat2x3 => gl::FLOAT_MAT2x3,
+            FloatMat2x4 => gl::FLOAT_MAT2x4,
+            FloatMat3x2 => gl::FLOAT_MAT3x2,
+            FloatMat3x4 => gl::FLOAT_MAT3x4,
+            FloatMat4x2 => gl::FLOAT_MAT4x2,
+            FloatMat4x3 => gl::FLOAT_MAT4x3,
+            Sampler1D => gl::SAMPLER_1D,
+            Sampler2D => gl::SAMPLER_2D,
+            Sampler3D => gl::SAMPLER_3D,
+            SamplerCube => gl::SAMPLER_CUBE,
+            Sampler1DArray => gl::SAMPLER_1D_ARRAY,
+            Sampler2DArray => gl::SAMPLER_2D_ARRAY,
+            Sampler2DRect => gl::SAMPLER_2D_RECT,
+            SamplerBuffer => gl::SAMPLER_BUFFER,
+            Sampler2DMultisample => gl::SAMPLER_2D_MULTISAMPLE,
+            Sampler1DShadow => gl::SAMPLER_1D_SHADOW,
+            Sampler2DShadow => gl::SAMPLER_2D_SHADOW,
+            SamplerCubeShadow => gl::SAMPLER_CUBE_SHADOW,
+            Sampler1DArrayShadow => gl::SAMPLER_1D_ARRAY_SHADOW,
+            Sampler2DArrayShadow => gl::SAMPLER_2D_ARRAY_SHADOW,
+            Sampler2DRectShadow => gl::SAMPLER_2D_RECT_SHADOW,
+            IntSampler1D => gl::INT_SAMPLER_1D,
+            IntSampler2D => gl::INT_SAMPLER_2D,
+            IntSampler3D => gl::INT_SAMPLER_3D,
+            IntSamplerCube => gl::INT_SAMPLER_CUBE,
+            IntSampler1DArray => gl::INT_SAMPLER_1D_ARRAY,
+            IntSampler2DArray => gl::INT_SAMPLER_2D_ARRAY,
+            IntSampler2DRect => gl::INT_SAMPLER_2D_RECT,
+            IntSamplerBuffer => gl::INT_SAMPLER_BUFFER,
+            IntSampler2DMultisample => gl::INT_SAMPLER_2D_MULTISAMPLE,
+            UnsignedIntSampler1D => gl::UNSIGNED_INT_SAMPLER_1D,
+            UnsignedIntSampler2D => gl::UNSIGNED_INT_SAMPLER_2D,
+            UnsignedIntSampler3D => gl::UNSIGNED_INT_SAMPLER_3D,
+            UnsignedIntSamplerCube => gl::UNSIGNED_INT_SAMPLER_CUBE,
+            UnsignedIntSampler1DArray => gl::UNSIGNED_INT_SAMPLER_1D_ARRAY,
+            UnsignedIntSampler2DArray => gl::UNSIGNED_INT_SAMPLER_2D_ARRAY,
+            UnsignedIntSampler2DRect => gl::UNSIGNED_INT_SAMPLER_2D_RECT,
+            UnsignedIntSamplerBuffer => gl::UNSIGNED_INT_SAMPLER_BUFFER,
+            UnsignedIntSampler2DMultisample => gl::UNSIGNED_INT_SAMPLER_2D_MULTISAMPLE,
+            UnknownType(ty) => ty,
+        }
+    }
+
+    /// Whether this is one of the sampler types, which are set with a
+    /// texture unit (see `uniform::Sampler`).
+    pub fn is_sampler(&self) -> bool {
+        match *self {
+            Sampler1D
+            | Sampler2D
+            | Sampler3D
+            | SamplerCube
+            | Sampler1DArray
+            | Sampler2DArray
+            | Sampler2DRect
+            | SamplerBuffer
+            | Sampler2DMultisample
+            | Sampler1DShadow
+            | Sampler2DShadow
+            | SamplerCubeShadow
+            | Sampler1DArrayShadow
+            | Sampler2DArrayShadow
+            | Sampler2DRectShadow
+            | IntSampler1D
+            | IntSampler2D
+            | IntSampler3D
+            | IntSamplerCube
+            | IntSampler1DArray
+            | IntSampler2DArray
+            | IntSampler2DRect
+            | IntSamplerBuffer
+            | IntSampler2DMultisample
+            | UnsignedIntSampler1D
+            | UnsignedIntSampler2D
+            | UnsignedIntSampler3D
+            | UnsignedIntSamplerCube
+            | UnsignedIntSampler1DArray
+            | UnsignedIntSampler2DArray
+            | UnsignedIntSampler2DRect
+            | UnsignedIntSamplerBuffer
+            | UnsignedIntSampler2DMultisample => true,
+            _ => false,
+        }
+    }
+}
+
+fn non_empty(log: &str) -> Option<&str> {
+    if log.trim().is_empty() { None } else { Some(log) }
+}
+
+/// A failure to compile a shader or link a program.
+#[deriving(Clone)]
+pub struct ShaderError {
+    /// The type of the shader that failed to compile, or None if linking
+    /// failed.
+    pub stage: Option<ShaderType>,
+    /// The source strings given to GL, indexed by the `source` of each
+    /// diagnostic. Empty if linking failed. For preprocessed shaders these
+    /// are the original files, not the combined text.
+    pub sources: Vec<String>,
+    /// Display names for `sources`, such as file names. May be empty.
+    pub names: Vec<String>,
+    /// The info log, exactly as GL reported it.
+    pub log: String,
+    /// The info log parsed into individual messages.
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// Number of source lines shown around each diagnostic.
+static CONTEXT_LINES: uint = 2;
+
+impl ShaderError {
+    fn new(stage: Option<ShaderType>, sources: Vec<String>, log: String) -> ShaderError {
+        ShaderError {
+            stage: stage,
+            sources: sources,
+            names: Vec::new(),
+            diagnostics: infolog::parse(log.as_slice()),
+            log: log,
+        }
+    }
+
+    fn source_name(&self, source: uint) -> String {
+        match self.names.as_slice().get(source) {
+            Some(name) => name.clone(),
+            None => source.to_string()
+        }
+    }
+
+    /// Print the lines of source string `source` surrounding `line`.
+    fn write_context(&self, f: &mut fmt::Formatter, source: uint, line: uint) -> fmt::Result {
+        if source >= self.sources.len() || line == 0 {
+            return Ok(());
+        }
+        let first = if line > CONTEXT_LINES { line - CONTEXT_LINES } else { 1 };
+        let lines = self.sources.as_slice()[source].as_slice().lines().enumerate();
+        for (i, text) in lines.skip(first - 1).take(line + CONTEXT_LINES + 1 - first) {
+            let marker = if i + 1 == line { ">" } else { " " };
+            try!(writeln!(f, "{} {:4} | {}", marker, i + 1, text));
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Show for ShaderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.stage {
+            Some(stage) => try!(writeln!(f, "failed to compile {}:", stage)),
+            None => try!(writeln!(f, "failed to link program:")),
+        }
+        for diag in self.diagnostics.iter() {
+            match (diag.source, diag.line) {
+                (Some(source), Some(line)) => {
+                    try!(writeln!(f, "{}:{}: {}: {}", self.source_name(source), line,
+                                  diag.severity, diag.message));
+                    try!(self.write_context(f, source, line));
+                },
+                _ => try!(writeln!(f, "{}", diag))
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The info log of a program or pipeline which failed validation.
+#[deriving(Clone)]
+pub struct ValidationError {
+    pub log: String,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl ValidationError {
+    fn new(log: String) -> ValidationError {
+        ValidationError { diagnostics: infolog::parse(log.as_slice()), log: log }
+    }
+}
+
+impl fmt::Show for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(writeln!(f, "program failed validation:"));
+        for diag in self.diagnostics.iter() {
+            try!(writeln!(f, "{}", diag));
+        }
+        Ok(())
+    }
+}
+
+static VALIDATE_DRAWS: AtomicBool = INIT_ATOMIC_BOOL;
+
+/// Turn draw validation on or off. When on, every `Vao` draw call first
+/// validates the bound program (or program pipeline) against the current
+/// state, and fails the task if it is invalid. This catches mistakes like
+/// two samplers of different types sharing a texture unit, which otherwise
+/// silently render nothing. It is slow, so only use it for debugging.
+pub fn set_validate_draws(enabled: bool) {
+    VALIDATE_DRAWS.store(enabled, SeqCst);
+}
+
+/// Whether draw validation is on.
+pub fn validate_draws() -> bool {
+    VALIDATE_DRAWS.load(SeqCst)
+}
+
+/// If draw validation is on, validate whatever program or pipeline the
+/// next draw call will use, failing the task if it is invalid.
+pub fn validate_for_draw() {
+    if !validate_draws() {
+        return;
+    }
+
+    let mut current = 0;
+    unsafe { gl::GetIntegerv(gl::CURRENT_PROGRAM, &mut current); }
+    let result = if current != 0 {
+        validate_program(current as GLuint)
+    } else if pipeline::is_supported() {
+        let mut pipeline = 0;
+        unsafe { gl::GetIntegerv(gl::PROGRAM_PIPELINE_BINDING, &mut pipeline); }
+        gl::ValidateProgramPipeline(pipeline as GLuint);
+        match get_info_log(pipeline as GLuint, gl::GetProgramPipelineiv,
+                           gl::GetProgramPipelineInfoLog, gl::VALIDATE_STATUS) {
+            (true, _) => Ok(()),
+            (false, log) => Err(Validation(ValidationError::new(log)))
+        }
+    } else {
+        Ok(())
+    };
+
+    match result {
+        Ok(()) => {},
+        Err(e) => fail!("{}", e)
+    }
+}
+
+fn validate_program(program: GLuint) -> Result<(), Error> {
+    gl::ValidateProgram(program);
+    match get_info_log(program, gl::GetProgramiv, gl::GetProgramInfoLog, gl::VALIDATE_STATUS) {
+        (true, _) => Ok(()),
+        (false, log) => Err(Validation(ValidationError::new(log)))
+    }
 }
 
 pub struct Shader {
     pub name: GLuint,
-    pub type_: ShaderType
+    pub type_: ShaderType,
+    log: String,
 }
 
+/// Check `status` of a shader or program, returning whether it is TRUE
+/// along with the (possibly empty) info log.
 fn get_info_log(shader: GLuint, get: unsafe fn(GLuint, GLenum, *mut GLint),
                 info: unsafe fn(GLuint, GLsizei, *mut GLint, *mut GLchar),
-                status: GLenum) -> Option<Vec<u8>> {
+                status: GLenum) -> (bool, String) {
     let mut ret = gl::FALSE as GLint;
     unsafe {
         get(shader, status, &mut ret);
     }
-
-    if ret == gl::TRUE as GLint {
-        return None
-    }
+    let ok = ret == gl::TRUE as GLint;
 
     let mut len = 0;
     unsafe {
         get(shader, gl::INFO_LOG_LENGTH, &mut len as *mut GLint);
     }
     if len == 0 {
-        return Some(Vec::new());
+        return (ok, String::new());
     }
 
     // len including trailing null
-    let mut s = Vec::with_capacity(len as uint - 1);
+    let mut s: Vec<u8> = Vec::with_capacity(len as uint);
 
     unsafe {
+        // len is set to the length excluding the trailing null
         info(shader, len, &mut len as *mut GLsizei, s.as_mut_slice().as_mut_ptr() as *mut GLchar);
-        s.set_len(len as uint - 1);
+        s.set_len(len as uint);
     }
-    Some(s)
+    (ok, String::from_utf8_lossy(s.as_slice()).into_string())
 }
 
 impl Shader {
-    pub fn from_name(name: GLuint, type_: ShaderType) -> Shader {
-        if cfg!(not(ndebug)) {
-            if gl::IsShader(name) == gl::FALSE {
-                fail!("name is not a shader!");
-            }
+    /// Wrap an existing shader object, which this Shader then owns.
+    pub fn from_name(name: GLuint, type_: ShaderType) -> Result<Shader, Error> {
+        if gl::IsShader(name) == gl::FALSE {
+            return Err(InvalidArgument(format!("{} is not a shader", name)));
         }
-        Shader::new_raw(name, type_)
+        Ok(Shader::new_raw(name, type_, String::new()))
     }
 
-    fn new_raw(id: GLuint, type_: ShaderType) -> Shader {
-        Shader { name: id, type_: type_ }
+    fn new_raw(id: GLuint, type_: ShaderType, log: String) -> Shader {
+        Shader { name: id, type_: type_, log: log }
     }
 
     /// Returns the name (id) of the shader.
@@ -74,11 +548,23 @@ impl Shader {
         self.name
     }
 
+    /// The info log of a successful compile, if the driver had anything to
+    /// say. This is usually warnings.
+    pub fn warnings(&self) -> Option<&str> {
+        non_empty(self.log.as_slice())
+    }
+
     /// Compile a shader.
     ///
     /// Takes the shader contents as a string. On success the Shader is returned.
-    /// On failure, the complete log from glGetShaderInfoLog is returned.
-    pub fn compile(source: &str, type_: ShaderType) -> Result<Shader, String> {
+    /// On failure, a `Compile` error holds the log from glGetShaderInfoLog
+    /// along with the source. Shader types the context doesn't support fail
+    /// with `MissingExtension` without calling GL.
+    pub fn compile(source: &str, type_: ShaderType) -> Result<Shader, Error> {
+        if !type_.is_supported() {
+            return Err(MissingExtension(type_.required_extension()));
+        }
+
         let gltype = type_.to_glenum();
         let shader = gl::CreateShader(gltype);
 
@@ -88,17 +574,258 @@ impl Shader {
         }
         gl::CompileShader(shader);
 
-        match get_info_log(shader, gl::GetShaderiv, gl::GetShaderInfoLog, gl::COMPILE_STATUS) {
-            Some(s) => Err(String::from_utf8(s).ok().expect("non-utf8 infolog!")),
-            None    => Ok(Shader::new_raw(shader, type_))
+        let result = match get_info_log(shader, gl::GetShaderiv, gl::GetShaderInfoLog,
+                                        gl::COMPILE_STATUS) {
+            (true, log) => Ok(Shader::new_raw(shader, type_, log)),
+            (false, log) => {
+                gl::DeleteShader(shader);
+                Err(Compile(ShaderError::new(Some(type_), vec![source.to_string()], log)))
+            }
+        };
+        try!(error::check("Shader::compile"));
+        result
+    }
+
+    /// Compile preprocessed source. Diagnostics refer to the original files.
+    pub fn compile_source(source: &Source, type_: ShaderType) -> Result<Shader, Error> {
+        Shader::compile(source.text.as_slice(), type_).map_err(|e| match e {
+            Compile(mut e) => {
+                e.sources = source.files.iter().map(|&(_, ref text)| text.clone()).collect();
+                e.names = source.files.iter().map(|&(ref path, _)| path.display().to_string()).collect();
+                Compile(e)
+            },
+            e => e
+        })
+    }
+
+    /// Read a shader from a file and compile it. `#include`s are resolved
+    /// relative to the file.
+    pub fn from_file(p: &str, type_: ShaderType) -> Result<Shader, Error> {
+        Shader::from_file_with(p, type_, &Preprocessor::new())
+    }
+
+    /// As `from_file`, but preprocessing with `pre`.
+    pub fn from_file_with(p: &str, type_: ShaderType,
+                          pre: &Preprocessor) -> Result<Shader, Error> {
+        let source = try!(pre.process_file(&Path::new(p)));
+        Shader::compile_source(&source, type_)
+    }
+}
+
+/// Shader source produced by a `Preprocessor`.
+pub struct Source {
+    /// The combined text, to be handed to GL.
+    pub text: String,
+    /// Every file that went into `text`, with its original contents. The
+    /// index of a file is its source string number in `#line` directives.
+    pub files: Vec<(Path, String)>,
+}
+
+/// Resolves `#include "file"` directives and injects `#define`s into shader
+/// sources.
+///
+/// Includes are resolved relative to the including file. Each file is
+/// included at most once, as if it had an include guard, and a file which
+/// (indirectly) includes itself is an error. `#line` directives are emitted
+/// around every include so that diagnostics point at the original file and
+/// line.
+///
+/// `#include`s inside block comments are left alone, but conditionals
+/// aren't evaluated: an `#include` inside `#if 0` is still included (and
+/// must exist).
+pub struct Preprocessor {
+    defines: Vec<(String, String)>,
+}
+
+/// Bookkeeping while expanding a single source.
+struct Expansion {
+    out: String,
+    files: Vec<(Path, String)>,
+    /// Files currently being expanded, for cycle detection.
+    stack: Vec<Path>,
+    /// The `#version` of the source, which decides how `#line` is numbered.
+    version: uint,
+    es: bool,
+}
+
+impl Expansion {
+    /// A `#line` directive such that the line after it is `next_line` of
+    /// source string `source`.
+    fn line_directive(&mut self, next_line: uint, source: uint) {
+        // before GLSL 3.30 (and ES 3.00), #line numbered the directive itself.
+        let line = if self.version < 330 && !(self.es && self.version >= 300) {
+            next_line - 1
+        } else {
+            next_line
+        };
+        self.out.push_str(format!("#line {} {}\n", line, source).as_slice());
+    }
+}
+
+fn preprocess_error(desc: &str, path: &Path, line: uint) -> Error {
+    InvalidArgument(format!("{}:{}: {}", path.display(), line, desc))
+}
+
+impl Preprocessor {
+    /// Create a preprocessor with no defines.
+    pub fn new() -> Preprocessor {
+        Preprocessor { defines: Vec::new() }
+    }
+
+    /// The defines added so far, as (name, value) pairs.
+    pub fn defines(&self) -> &[(String, String)] {
+        self.defines.as_slice()
+    }
+
+    /// Add `#define name value`, to be inserted after the `#version` line.
+    pub fn define(self, name: &str, value: &str) -> Preprocessor {
+        let mut defines = self.defines;
+        defines.push((name.to_string(), value.to_string()));
+        Preprocessor { defines: defines }
+    }
+
+    /// Read and preprocess the file at `path`.
+    pub fn process_file(&self, path: &Path) -> Result<Source, Error> {
+        let text = try!(File::open(path).read_to_string().map_err(Io));
+        self.process(text.as_slice(), path)
+    }
+
+    /// Preprocess `source` as if it were read from `path`.
+    pub fn process(&self, source: &str, path: &Path) -> Result<Source, Error> {
+        let mut st = Expansion {
+            out: String::new(),
+            files: vec![(path.clone(), source.to_string())],
+            stack: vec![path.clone()],
+            version: 110,
+            es: false,
+        };
+
+        // #version must come first, so the defines go right after it (or
+        // at the very start, if there is no #version).
+        let version_line = source.lines().position(|l| directive(l, "version").is_some());
+        match version_line {
+            Some(i) => {
+                let line = source.lines().nth(i).unwrap();
+                let args = directive(line, "version").unwrap();
+                st.version = args.words().next().and_then(|v| from_str(v)).unwrap_or(110);
+                st.es = args.words().nth(1) == Some("es");
+            },
+            None => self.write_defines(&mut st, 1),
         }
+
+        try!(self.expand(source, path, 0, version_line, &mut st));
+        Ok(Source { text: st.out, files: st.files })
+    }
+
+    fn write_defines(&self, st: &mut Expansion, next_line: uint) {
+        if self.defines.is_empty() {
+            return;
+        }
+        for &(ref name, ref value) in self.defines.iter() {
+            st.out.push_str(format!("#define {} {}\n", name, value).as_slice());
+        }
+        st.line_directive(next_line, 0);
     }
 
-    pub fn from_file(p: &str, type_: ShaderType) -> IoResult<Result<Shader, String>> {
-        match File::open(&Path::new(p)).read_to_string() {
-            Err(e) => Err(e),
-            Ok(s) => Ok(Shader::compile(s.as_slice(), type_))
+    /// Append `source`, read from `here` and given source string number
+    /// `idx`, to the output, expanding includes.
+    fn expand(&self, source: &str, here: &Path, idx: uint, version_line: Option<uint>,
+              st: &mut Expansion) -> Result<(), Error> {
+        let mut in_comment = false;
+        for (i, line) in source.lines().enumerate() {
+            let lineno = i + 1;
+            let commented = in_comment;
+            in_comment = in_block_comment(line, in_comment);
+            match if commented { None } else { directive(line, "include") } {
+                None => {
+                    st.out.push_str(line);
+                    st.out.push_char('\n');
+                    if Some(i) == version_line {
+                        self.write_defines(st, lineno + 1);
+                    }
+                },
+                Some(args) => {
+                    let path = match include_path(args) {
+                        Some(file) => here.dir_path().join(file),
+                        None => return Err(preprocess_error("malformed #include", here, lineno)),
+                    };
+
+                    if st.stack.contains(&path) {
+                        return Err(preprocess_error("#include cycle", here, lineno));
+                    }
+                    if st.files.iter().any(|&(ref p, _)| *p == path) {
+                        // already included; keep the line count intact
+                        st.out.push_char('\n');
+                        continue;
+                    }
+
+                    let text = try!(File::open(&path).read_to_string().map_err(Io));
+                    let child = st.files.len();
+                    st.files.push((path.clone(), text.clone()));
+                    st.stack.push(path.clone());
+                    st.line_directive(1, child);
+                    try!(self.expand(text.as_slice(), &path, child, None, st));
+                    st.stack.pop();
+                    st.line_directive(lineno + 1, idx);
+                }
+            }
         }
+        Ok(())
+    }
+}
+
+/// The file named by the arguments of an `#include`, which may be followed
+/// by a comment.
+fn include_path<'a>(args: &'a str) -> Option<&'a str> {
+    if !args.starts_with("\"") {
+        return None;
+    }
+    let end = match args.slice_from(1).find('"') {
+        Some(0) | None => return None,
+        Some(i) => i + 1,
+    };
+    let rest = args.slice_from(end + 1).trim_left();
+    if rest.is_empty() || rest.starts_with("//") || rest.starts_with("/*") {
+        Some(args.slice(1, end))
+    } else {
+        None
+    }
+}
+
+/// Whether a block comment is open at the end of `line`, given whether one
+/// was open at its start.
+fn in_block_comment(line: &str, mut open: bool) -> bool {
+    let bytes = line.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        let next = if i + 1 < bytes.len() { bytes[i + 1] } else { 0 };
+        if open {
+            if bytes[i] == '*' as u8 && next == '/' as u8 {
+                open = false;
+                i += 1;
+            }
+        } else if bytes[i] == '/' as u8 && next == '/' as u8 {
+            break;
+        } else if bytes[i] == '/' as u8 && next == '*' as u8 {
+            open = true;
+            i += 1;
+        }
+        i += 1;
+    }
+    open
+}
+
+/// If `line` is the preprocessor directive `name`, returns its arguments.
+fn directive<'a>(line: &'a str, name: &str) -> Option<&'a str> {
+    let line = line.trim();
+    if !line.starts_with("#") {
+        return None;
+    }
+    let rest = line.slice_from(1).trim_left();
+    if rest.starts_with(name) && rest.slice_from(name.len()).chars().next().map_or(true, |c| c.is_whitespace()) {
+        Some(rest.slice_from(name.len()).trim())
+    } else {
+        None
     }
 }
 
@@ -108,46 +835,456 @@ impl Drop for Shader {
     }
 }
 
+/// An active uniform or vertex attribute of a linked program.
+pub struct ActiveVariable {
+    /// The name, as reported by GL. Arrays are reported as `name[0]`.
+    pub name: String,
+    pub type_: GlslType,
+    /// The number of array elements, or 1 if this isn't an array.
+    pub size: GLint,
+    pub location: GLint,
+}
+
+/// Query every active uniform or attribute of `program`.
+fn active_variables(program: GLuint, count: GLenum, max_length: GLenum,
+                    get: unsafe fn(GLuint, GLuint, GLsizei, *mut GLsizei,
+                                   *mut GLint, *mut GLenum, *mut GLchar),
+                    get_location: unsafe fn(GLuint, *const GLchar) -> GLint)
+                    -> Vec<ActiveVariable> {
+    let mut num = 0;
+    let mut max_len = 0;
+    unsafe {
+        gl::GetProgramiv(program, count, &mut num);
+        gl::GetProgramiv(program, max_length, &mut max_len);
+    }
+
+    let mut vars = Vec::with_capacity(num as uint);
+    let mut buf = Vec::from_elem(max_len as uint, 0u8);
+    for i in range(0, num as GLuint) {
+        let mut len = 0;
+        let mut size = 0;
+        let mut type_ = 0;
+        unsafe {
+            get(program, i, max_len, &mut len, &mut size, &mut type_,
+                buf.as_mut_slice().as_mut_ptr() as *mut GLchar);
+        }
+        let name = String::from_utf8_lossy(buf.as_slice().slice_to(len as uint)).into_string();
+        let location = name.with_c_str(|cstr| unsafe { get_location(program, cstr) });
+        vars.push(ActiveVariable {
+            name: name,
+            type_: GlslType::from_glenum(type_),
+            size: size,
+            location: location,
+        });
+    }
+    vars
+}
+
+/// Build a name to location map for `vars`. Arrays are reported by GL as
+/// `name[0]`; they are also entered as `name` and `name[i]` for every
+/// element, so that any spelling a caller might use is a cache hit.
+fn locations(program: GLuint, vars: &[ActiveVariable],
+             get: unsafe fn(GLuint, *const GLchar) -> GLint) -> HashMap<String, GLint> {
+    let lookup = |name: &str| name.with_c_str(|cstr| unsafe { get(program, cstr) });
+
+    let mut map = HashMap::new();
+    for var in vars.iter() {
+        let name = var.name.as_slice();
+        map.insert(var.name.clone(), var.location);
+        if name.ends_with("[0]") {
+            let base = name.slice_to(name.len() - 3);
+            map.insert(base.to_string(), var.location);
+            for i in range(1, var.size) {
+                let elt = format!("{}[{}]", base, i);
+                let loc = lookup(elt.as_slice());
+                map.insert(elt, loc);
+            }
+        }
+    }
+    map
+}
+
+/// A subroutine uniform in one stage of a program, along with the
+/// subroutines that can be assigned to it.
+pub struct SubroutineUniform {
+    pub name: String,
+    pub location: GLint,
+    /// The number of array elements, or 1 if this isn't an array.
+    pub size: GLint,
+    /// The name and index of every compatible subroutine.
+    pub compatible: Vec<(String, GLuint)>,
+}
+
+/// The subroutine uniforms of one stage, and the subroutine currently
+/// selected for each of its locations.
+struct StageSubroutines {
+    stage: ShaderType,
+    uniforms: Vec<SubroutineUniform>,
+    selected: RefCell<Vec<GLuint>>,
+}
+
+impl StageSubroutines {
+    /// Upload the selected subroutines. GL forgets them whenever a program
+    /// is bound, so this has to happen after every glUseProgram.
+    fn upload(&self) {
+        let selected = self.selected.borrow();
+        unsafe {
+            gl::UniformSubroutinesuiv(self.stage.to_glenum(), selected.len() as GLsizei,
+                                      selected.as_ptr());
+        }
+    }
+}
+
+fn subroutines_supported() -> bool {
+    context::supports_version(4, 0) || context::has_extension("GL_ARB_shader_subroutine")
+}
+
+/// Read a name of at most `max_len` bytes (including the null) with `get`,
+/// which is passed the buffer size, a pointer to the length and the buffer.
+fn read_name(max_len: GLint, get: |GLsizei, *mut GLsizei, *mut GLchar|) -> String {
+    let mut buf = Vec::from_elem(max_len as uint, 0u8);
+    let mut len = 0;
+    get(max_len, &mut len, buf.as_mut_slice().as_mut_ptr() as *mut GLchar);
+    String::from_utf8_lossy(buf.as_slice().slice_to(len as uint)).into_string()
+}
+
+impl SubroutineUniform {
+    /// The location of `name`, which is either this uniform or, for arrays,
+    /// one of its elements. GL may report an array as `name[0]`, but plain
+    /// `name` also means the first element, as with `Program::uniform`.
+    fn location_of(&self, name: &str) -> Option<GLint> {
+        let full = self.name.as_slice();
+        let base = if full.ends_with("[0]") { full.slice_to(full.len() - 3) } else { full };
+        if name == full || name == base {
+            return Some(self.location);
+        }
+        if name.starts_with(base) && name.ends_with("]") {
+            let index = name.slice(base.len(), name.len() - 1);
+            if index.starts_with("[") {
+                match from_str::<GLint>(index.slice_from(1)) {
+                    Some(i) if i >= 0 && i < self.size => return Some(self.location + i),
+                    _ => {}
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Query the subroutine uniforms of `stage` in `program`, selecting the
+/// first compatible subroutine for each.
+fn query_subroutines(program: GLuint, stage: ShaderType) -> StageSubroutines {
+    let shadertype = stage.to_glenum();
+    let stage_param = |pname: GLenum| {
+        let mut value = 0;
+        unsafe { gl::GetProgramStageiv(program, shadertype, pname, &mut value); }
+        value
+    };
+    let num_uniforms = stage_param(gl::ACTIVE_SUBROUTINE_UNIFORMS);
+    let num_locations = stage_param(gl::ACTIVE_SUBROUTINE_UNIFORM_LOCATIONS);
+    let uniform_max_len = stage_param(gl::ACTIVE_SUBROUTINE_UNIFORM_MAX_LENGTH);
+    let subroutine_max_len = stage_param(gl::ACTIVE_SUBROUTINE_MAX_LENGTH);
+
+    let mut uniforms = Vec::with_capacity(num_uniforms as uint);
+    let mut selected = Vec::from_elem(num_locations as uint, 0 as GLuint);
+    for i in range(0, num_uniforms as GLuint) {
+        let uniform_param = |pname: GLenum| {
+            let mut value = 0;
+            unsafe { gl::GetActiveSubroutineUniformiv(program, shadertype, i, pname, &mut value); }
+            value
+        };
+
+        let name = read_name(uniform_max_len, |size, len, buf| unsafe {
+            gl::GetActiveSubroutineUniformName(program, shadertype, i, size, len, buf)
+        });
+        let location = name.with_c_str(|cstr| unsafe {
+            gl::GetSubroutineUniformLocation(program, shadertype, cstr)
+        });
+        let size = uniform_param(gl::UNIFORM_SIZE);
+
+        let mut indices = Vec::from_elem(uniform_param(gl::NUM_COMPATIBLE_SUBROUTINES) as uint, 0 as GLint);
+        unsafe {
+            gl::GetActiveSubroutineUniformiv(program, shadertype, i, gl::COMPATIBLE_SUBROUTINES,
+                                             indices.as_mut_slice().as_mut_ptr());
+        }
+        let compatible: Vec<(String, GLuint)> = indices.iter().map(|&index| {
+            let name = read_name(subroutine_max_len, |size, len, buf| unsafe {
+                gl::GetActiveSubroutineName(program, shadertype, index as GLuint, size, len, buf)
+            });
+            (name, index as GLuint)
+        }).collect();
+
+        match compatible.as_slice().get(0) {
+            Some(&(_, first)) if location >= 0 => {
+                for loc in range(location, location + size) {
+                    selected.as_mut_slice()[loc as uint] = first;
+                }
+            },
+            _ => {}
+        }
+        uniforms.push(SubroutineUniform {
+            name: name,
+            location: location,
+            size: size,
+            compatible: compatible,
+        });
+    }
+
+    StageSubroutines { stage: stage, uniforms: uniforms, selected: RefCell::new(selected) }
+}
+
 /// A program, which consists of multiple compiled shaders "linked" together
 pub struct Program {
-    pub name: GLuint
+    pub name: GLuint,
+    stages: Vec<ShaderType>,
+    log: String,
+    active_uniforms: Vec<ActiveVariable>,
+    active_attributes: Vec<ActiveVariable>,
+    uniforms: HashMap<String, GLint>,
+    attributes: HashMap<String, GLint>,
+    subroutines: Vec<StageSubroutines>,
 }
 
 impl Program {
     /// Link shaders into a program
-    pub fn link(shaders: &[Result<Shader, String>]) -> Result<Program, String> {
+    ///
+    /// Any combination of stages may be linked, except that compute shaders
+    /// can only be linked with other compute shaders.
+    pub fn link(shaders: &[Result<Shader, Error>]) -> Result<Program, Error> {
+        ProgramBuilder::new().shaders(shaders).link()
+    }
+
+    /// Load a program from a binary previously returned by `binary`.
+    /// `stages` are the shader types the binary was linked from.
+    ///
+    /// Drivers are free to reject binaries, for example after a driver
+    /// update, so be prepared to link from source instead.
+    pub fn from_binary(format: GLenum, binary: &[u8],
+                       stages: &[ShaderType]) -> Result<Program, Error> {
         let program = gl::CreateProgram();
-        for shader in shaders.iter() {
-            match shader {
-                &Ok(ref shader) => {
-                    // there are no relevant errors to handle here.
-                    gl::AttachShader(program, shader.name);
-                },
-                &Err(ref e) => return Err(e.clone())
+        unsafe {
+            gl::ProgramBinary(program, format, binary.as_ptr() as *const c_void,
+                              binary.len() as GLsizei);
+        }
+
+        let result = match get_info_log(program, gl::GetProgramiv, gl::GetProgramInfoLog,
+                                        gl::LINK_STATUS) {
+            (true, log) => Ok(Program::from_linked(program, stages.to_vec(), log)),
+            (false, log) => {
+                gl::DeleteProgram(program);
+                Err(Link(ShaderError::new(None, Vec::new(), log)))
             }
+        };
+        try!(error::check("Program::from_binary"));
+        result
+    }
+
+    /// Retrieve the driver-specific binary of this program, along with its
+    /// format, for use with `from_binary`. Returns None if the driver has no
+    /// binary to give.
+    pub fn binary(&self) -> Option<(GLenum, Vec<u8>)> {
+        let mut len = 0;
+        unsafe { gl::GetProgramiv(self.name, gl::PROGRAM_BINARY_LENGTH, &mut len); }
+        if len == 0 {
+            return None;
         }
-        gl::LinkProgram(program);
 
-        match get_info_log(program, gl::GetProgramiv, gl::GetProgramInfoLog, gl::LINK_STATUS) {
-            Some(s) => Err(String::from_utf8(s).ok().expect("non-utf8 infolog!")),
-            None    => Ok(Program { name: program })
+        let mut data: Vec<u8> = Vec::with_capacity(len as uint);
+        let mut format = 0;
+        unsafe {
+            gl::GetProgramBinary(self.name, len, &mut len, &mut format,
+                                 data.as_mut_slice().as_mut_ptr() as *mut c_void);
+            data.set_len(len as uint);
         }
+        error::check_or_fail("Program::binary");
+        Some((format, data))
     }
 
+    /// Wrap a successfully linked program, caching its active uniforms and
+    /// attributes along with their locations.
+    fn from_linked(program: GLuint, stages: Vec<ShaderType>, log: String) -> Program {
+        let uniforms = active_variables(program, gl::ACTIVE_UNIFORMS,
+                                        gl::ACTIVE_UNIFORM_MAX_LENGTH,
+                                        gl::GetActiveUniform, gl::GetUniformLocation);
+        let attributes = active_variables(program, gl::ACTIVE_ATTRIBUTES,
+                                          gl::ACTIVE_ATTRIBUTE_MAX_LENGTH,
+                                          gl::GetActiveAttrib, gl::GetAttribLocation);
+        let subroutines = if subroutines_supported() {
+            stages.iter().map(|&stage| query_subroutines(program, stage))
+                         .filter(|s| !s.uniforms.is_empty())
+                         .collect()
+        } else {
+            Vec::new()
+        };
+        Program {
+            name: program,
+            stages: stages,
+            subroutines: subroutines,
+            log: log,
+            uniforms: locations(program, uniforms.as_slice(), gl::GetUniformLocation),
+            attributes: locations(program, attributes.as_slice(), gl::GetAttribLocation),
+            active_uniforms: uniforms,
+            active_attributes: attributes,
+        }
+    }
+
+    /// All active uniforms of this program. Uniforms in named uniform blocks
+    /// are included, with a location of -1.
+    pub fn active_uniforms(&self) -> &[ActiveVariable] {
+        self.active_uniforms.as_slice()
+    }
+
+    /// All active vertex attributes of this program.
+    pub fn active_attributes(&self) -> &[ActiveVariable] {
+        self.active_attributes.as_slice()
+    }
+
+    /// The info log of a successful link, if the driver had anything to
+    /// say. This is usually warnings.
+    pub fn warnings(&self) -> Option<&str> {
+        non_empty(self.log.as_slice())
+    }
+
+    /// Whether a shader of type `stage` was linked into this program.
+    pub fn has_stage(&self, stage: ShaderType) -> bool {
+        self.stages.contains(&stage)
+    }
+
+    /// Whether this program was linked with `ProgramBuilder::separable`.
+    pub fn is_separable(&self) -> bool {
+        let mut separable = gl::FALSE as GLint;
+        unsafe { gl::GetProgramiv(self.name, gl::PROGRAM_SEPARABLE, &mut separable); }
+        separable == gl::TRUE as GLint
+    }
+
+    /// Bind this program, and upload its selected subroutines.
     pub fn bind(&self) {
         gl::UseProgram(self.name);
+        for stage in self.subroutines.iter() {
+            stage.upload();
+        }
+        error::check_or_fail("Program::bind");
+    }
+
+    /// Label this program for debug messages and debuggers. Requires GL
+    /// 4.3 or KHR_debug.
+    pub fn label(&self, label: &str) {
+        debug::object_label(gl::PROGRAM, self.name, label);
+    }
+
+    /// The active subroutine uniforms of `stage`. Empty if the stage has
+    /// none, or the context doesn't support subroutines (GL 4.0 or
+    /// ARB_shader_subroutine).
+    pub fn subroutine_uniforms(&self, stage: ShaderType) -> &[SubroutineUniform] {
+        match self.subroutines.iter().find(|s| s.stage == stage) {
+            Some(s) => s.uniforms.as_slice(),
+            None => &[]
+        }
+    }
+
+    /// Select `subroutine` for the subroutine uniform `uniform` in `stage`,
+    /// and bind the program. For arrays, `uniform` may name an element as
+    /// `name[i]`; plain `name` sets the first element. The
+    /// selection is remembered, and uploaded again every time the program is
+    /// bound.
+    pub fn set_subroutine(&self, stage: ShaderType, uniform: &str,
+                          subroutine: &str) -> Result<(), Error> {
+        let st = match self.subroutines.iter().find(|s| s.stage == stage) {
+            Some(st) => st,
+            None => return Err(InvalidArgument(format!("{} has no subroutine uniforms", stage)))
+        };
+        let (u, location) = match st.uniforms.iter()
+                                      .filter_map(|u| u.location_of(uniform).map(|l| (u, l)))
+                                      .next() {
+            Some(found) => found,
+            None => return Err(InvalidArgument(format!("no subroutine uniform named {} in {}",
+                                                       uniform, stage)))
+        };
+        if location < 0 || location as uint >= st.selected.borrow().len() {
+            return Err(InvalidArgument(format!("subroutine uniform {} in {} has no location",
+                                               uniform, stage)));
+        }
+        let index = match u.compatible.iter().find(|&&(ref n, _)| n.as_slice() == subroutine) {
+            Some(&(_, index)) => index,
+            None => return Err(InvalidArgument(format!("subroutine {} is not compatible with {}",
+                                                       subroutine, uniform)))
+        };
+        st.selected.borrow_mut().as_mut_slice()[location as uint] = index;
+        self.bind();
+        error::check("Program::set_subroutine")
     }
 
+    /// Check whether this program can execute given the current GL state,
+    /// such as which textures are bound to the units its samplers use.
+    pub fn validate(&self) -> Result<(), Error> {
+        try!(validate_program(self.name));
+        error::check("Program::validate")
+    }
+
+    /// Bind this program and launch `x * y * z` work groups of its compute
+    /// shader. The program must have been linked from compute shaders only.
+    pub fn dispatch_compute(&self, x: GLuint, y: GLuint, z: GLuint) -> Result<(), Error> {
+        if !self.has_stage(ComputeShader) {
+            return Err(InvalidArgument("dispatch_compute on a program without a compute shader".to_string()));
+        }
+        self.bind();
+        gl::DispatchCompute(x, y, z);
+        error::check("Program::dispatch_compute")
+    }
+
+    /// Bind the fragment shader output `name` to `color_number`. Like
+    /// glBindFragDataLocation this only takes effect the next time the
+    /// program is linked; use `ProgramBuilder::frag_data_location` instead.
     pub fn bind_frag(&self, color_number: GLuint, name: &str) {
         name.with_c_str(|cstr| unsafe {
             gl::BindFragDataLocation(self.name, color_number, cstr)
         });
+        error::check_or_fail("Program::bind_frag");
     }
 
+    /// The location of the uniform `name`, or -1 if it is not an active
+    /// uniform. Locations are cached at link time, so this never queries GL.
     pub fn uniform(&self, name: &str) -> GLint {
-        name.with_c_str(|cstr| unsafe {
-            gl::GetUniformLocation(self.name, cstr)
-        })
+        match self.uniforms.find_equiv(&name) {
+            Some(&loc) => loc,
+            None => -1
+        }
+    }
+
+    /// The location of the vertex attribute `name`, or -1 if it is not an
+    /// active attribute. Like `uniform`, this is served from the cache.
+    pub fn attrib(&self, name: &str) -> GLint {
+        match self.attributes.find_equiv(&name) {
+            Some(&loc) => loc,
+            None => -1
+        }
+    }
+
+    /// Have the uniform block `name` read from the buffer bound to
+    /// `binding` (see `UniformBuffer::bind_base`). Fails, doing nothing, if
+    /// there is no active block called `name`.
+    pub fn bind_uniform_block(&self, name: &str, binding: GLuint) -> Result<(), Error> {
+        let index = name.with_c_str(|cstr| unsafe {
+            gl::GetUniformBlockIndex(self.name, cstr)
+        });
+        if index == gl::INVALID_INDEX {
+            return Err(InvalidArgument(format!("no active uniform block named {}", name)));
+        }
+        gl::UniformBlockBinding(self.name, index, binding);
+        error::check("Program::bind_uniform_block")
+    }
+
+    /// Set the uniform `name` to `value`. This binds the program, and picks
+    /// the glUniform* call matching the type of `value`. Fails, doing
+    /// nothing, if there is no active uniform called `name`.
+    pub fn set_uniform<T: Uniform>(&self, name: &str, value: T) -> Result<(), Error> {
+        let location = self.uniform(name);
+        if location < 0 {
+            return Err(InvalidArgument(format!("no active uniform named {}", name)));
+        }
+        self.bind();
+        value.set(location);
+        error::check("Program::set_uniform")
     }
 
     pub fn get_name(&self) -> GLuint { self.name }
@@ -159,3 +1296,234 @@ impl Drop for Program {
     }
 }
 
+/// Shaders plus the settings which must be made before linking a program.
+///
+/// Note that this struct uses the builder pattern. Its intended usage is:
+///
+///     let program = ProgramBuilder::new().shaders(&[vs, fs])
+///                                        .attrib_location("position", 0)
+///                                        .frag_data_location("out_color", 0)
+///                                        .link();
+///
+/// Fixing locations this way keeps them the same across every program
+/// built with the same bindings, instead of being picked by the driver.
+pub struct ProgramBuilder<'a> {
+    shaders: Vec<&'a Result<Shader, Error>>,
+    attrib_locations: Vec<(String, GLuint)>,
+    frag_data_locations: Vec<(String, GLuint)>,
+    feedback: Option<(Vec<String>, FeedbackMode)>,
+    separable: bool,
+    retrievable: bool,
+}
+
+impl<'a> ProgramBuilder<'a> {
+    /// Create a builder with no shaders and no bindings.
+    pub fn new() -> ProgramBuilder<'a> {
+        ProgramBuilder {
+            shaders: Vec::new(),
+            attrib_locations: Vec::new(),
+            frag_data_locations: Vec::new(),
+            feedback: None,
+            separable: false,
+            retrievable: false,
+        }
+    }
+
+    /// Add a shader. If it failed to compile, `link` returns its error.
+    pub fn shader(mut self, shader: &'a Result<Shader, Error>) -> ProgramBuilder<'a> {
+        self.shaders.push(shader);
+        self
+    }
+
+    /// Add several shaders.
+    pub fn shaders(mut self, shaders: &'a [Result<Shader, Error>]) -> ProgramBuilder<'a> {
+        self.shaders.extend(shaders.iter());
+        self
+    }
+
+    /// Bind the vertex attribute `name` to `index` (glBindAttribLocation).
+    pub fn attrib_location(mut self, name: &str, index: GLuint) -> ProgramBuilder<'a> {
+        self.attrib_locations.push((name.to_string(), index));
+        self
+    }
+
+    /// Bind the fragment shader output `name` to `color_number`
+    /// (glBindFragDataLocation).
+    pub fn frag_data_location(mut self, name: &str, color_number: GLuint) -> ProgramBuilder<'a> {
+        self.frag_data_locations.push((name.to_string(), color_number));
+        self
+    }
+
+    /// Capture the vertex shader (or last pre-rasterization stage) outputs
+    /// `varyings` with transform feedback, laid out according to `mode`.
+    pub fn feedback_varyings(self, varyings: &[&str], mode: FeedbackMode) -> ProgramBuilder<'a> {
+        let varyings = varyings.iter().map(|v| v.to_string()).collect();
+        ProgramBuilder { feedback: Some((varyings, mode)), ..self }
+    }
+
+    /// Link a separable program, whose stages can be used in a
+    /// `ProgramPipeline`. Requires GL 4.1 or ARB_separate_shader_objects.
+    pub fn separable(self, separable: bool) -> ProgramBuilder<'a> {
+        ProgramBuilder { separable: separable, ..self }
+    }
+
+    /// Hint to the driver that `Program::binary` will be called on the
+    /// result.
+    pub fn binary_retrievable(self, retrievable: bool) -> ProgramBuilder<'a> {
+        ProgramBuilder { retrievable: retrievable, ..self }
+    }
+
+    /// Link the shaders into a program.
+    ///
+    /// Any combination of stages may be linked, except that compute shaders
+    /// can only be linked with other compute shaders.
+    pub fn link(&self) -> Result<Program, Error> {
+        let mut names = Vec::with_capacity(self.shaders.len());
+        let mut stages = Vec::new();
+        for shader in self.shaders.iter() {
+            match **shader {
+                Ok(ref shader) => {
+                    names.push(shader.name);
+                    if !stages.contains(&shader.type_) {
+                        stages.push(shader.type_);
+                    }
+                },
+                Err(ref e) => return Err(e.clone())
+            }
+        }
+        if stages.contains(&ComputeShader) && stages.len() > 1 {
+            return Err(Link(ShaderError::new(None, Vec::new(),
+                                             "compute shaders cannot be linked with other stages".to_string())));
+        }
+
+        let program = gl::CreateProgram();
+        for &name in names.iter() {
+            // there are no relevant errors to handle here.
+            gl::AttachShader(program, name);
+        }
+        for &(ref name, index) in self.attrib_locations.iter() {
+            name.with_c_str(|cstr| unsafe { gl::BindAttribLocation(program, index, cstr) });
+        }
+        for &(ref name, color_number) in self.frag_data_locations.iter() {
+            name.with_c_str(|cstr| unsafe { gl::BindFragDataLocation(program, color_number, cstr) });
+        }
+        match self.feedback {
+            Some((ref varyings, mode)) => {
+                let cstrs: Vec<CString> = varyings.iter().map(|v| v.to_c_str()).collect();
+                let ptrs: Vec<*const GLchar> = cstrs.iter().map(|c| c.as_ptr()).collect();
+                unsafe {
+                    gl::TransformFeedbackVaryings(program, ptrs.len() as GLsizei,
+                                                  ptrs.as_ptr(), mode.to_glenum());
+                }
+            },
+            None => {}
+        }
+        if self.separable {
+            gl::ProgramParameteri(program, gl::PROGRAM_SEPARABLE, gl::TRUE as GLint);
+        }
+        if self.retrievable {
+            gl::ProgramParameteri(program, gl::PROGRAM_BINARY_RETRIEVABLE_HINT, gl::TRUE as GLint);
+        }
+        gl::LinkProgram(program);
+
+        let result = match get_info_log(program, gl::GetProgramiv, gl::GetProgramInfoLog,
+                                        gl::LINK_STATUS) {
+            (true, log) => Ok(Program::from_linked(program, stages, log)),
+            (false, log) => {
+                gl::DeleteProgram(program);
+                Err(Link(ShaderError::new(None, Vec::new(), log)))
+            }
+        };
+        try!(error::check("ProgramBuilder::link"));
+        result
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::Preprocessor;
+    use std::io::{File, TempDir};
+
+    fn process(pre: &Preprocessor, source: &str) -> String {
+        pre.process(source, &Path::new("main.glsl")).unwrap().text
+    }
+
+    #[test]
+    fn unchanged_without_defines() {
+        let source = "#version 330\nvoid main() {}\n";
+        assert_eq!(process(&Preprocessor::new(), source).as_slice(), source);
+    }
+
+    #[test]
+    fn defines_after_version() {
+        let pre = Preprocessor::new().define("FOO", "1");
+        assert_eq!(process(&pre, "#version 330\nvoid main() {}").as_slice(),
+                   "#version 330\n#define FOO 1\n#line 2 0\nvoid main() {}\n");
+        assert_eq!(process(&pre, "#version 300 es\nvoid main() {}").as_slice(),
+                   "#version 300 es\n#define FOO 1\n#line 2 0\nvoid main() {}\n");
+    }
+
+    #[test]
+    fn old_line_numbering() {
+        // before 3.30, #line N means the next line is N + 1
+        let pre = Preprocessor::new().define("FOO", "1");
+        assert_eq!(process(&pre, "#version 150\nvoid main() {}").as_slice(),
+                   "#version 150\n#define FOO 1\n#line 1 0\nvoid main() {}\n");
+        assert_eq!(process(&pre, "#version 100 es\nvoid main() {}").as_slice(),
+                   "#version 100 es\n#define FOO 1\n#line 1 0\nvoid main() {}\n");
+    }
+
+    #[test]
+    fn defines_without_version() {
+        let pre = Preprocessor::new().define("FOO", "1");
+        assert_eq!(process(&pre, "void main() {}").as_slice(),
+                   "#define FOO 1\n#line 0 0\nvoid main() {}\n");
+    }
+
+    #[test]
+    fn include_in_comment() {
+        let source = "/*\n#include \"missing.glsl\"\n*/\n// #include \"missing.glsl\"\n";
+        assert_eq!(process(&Preprocessor::new(), source).as_slice(), source);
+    }
+
+    #[test]
+    fn malformed_include() {
+        let main = Path::new("main.glsl");
+        assert!(Preprocessor::new().process("#include <a.glsl>", &main).is_err());
+        assert!(Preprocessor::new().process("#include \"a.glsl\" b", &main).is_err());
+        assert!(Preprocessor::new().process("#include \"\"", &main).is_err());
+    }
+
+    #[test]
+    fn include_with_comment() {
+        let dir = TempDir::new("hgl").unwrap();
+        File::create(&dir.path().join("a.glsl")).write_str("float a;\n").unwrap();
+        let main = dir.path().join("main.glsl");
+        let source = "#version 330\n#include \"a.glsl\" // phong\nvoid main() {}";
+        assert_eq!(Preprocessor::new().process(source, &main).unwrap().text.as_slice(),
+                   "#version 330\n#line 1 1\nfloat a;\n#line 3 0\nvoid main() {}\n");
+        let source = "#version 330\n#include \"a.glsl\" /* phong */\nvoid main() {}";
+        assert_eq!(Preprocessor::new().process(source, &main).unwrap().text.as_slice(),
+                   "#version 330\n#line 1 1\nfloat a;\n#line 3 0\nvoid main() {}\n");
+    }
+
+    #[test]
+    fn include() {
+        let dir = TempDir::new("hgl").unwrap();
+        File::create(&dir.path().join("a.glsl")).write_str("float a;\n").unwrap();
+        let main = dir.path().join("main.glsl");
+        let pre = Preprocessor::new();
+
+        let source = pre.process("#version 330\n#include \"a.glsl\"\nvoid main() {}", &main).unwrap();
+        assert_eq!(source.text.as_slice(),
+                   "#version 330\n#line 1 1\nfloat a;\n#line 3 0\nvoid main() {}\n");
+        assert_eq!(source.files.len(), 2);
+        let (_, ref text) = source.files.as_slice()[1];
+        assert_eq!(text.as_slice(), "float a;\n");
+
+        let source = pre.process("#version 150\n#include \"a.glsl\"\nvoid main() {}", &main).unwrap();
+        assert_eq!(source.text.as_slice(),
+                   "#version 150\n#line 0 1\nfloat a;\n#line 2 0\nvoid main() {}\n");
+    }
+}
@@ -1,14 +1,22 @@
 use gl;
 use gl::types::{GLuint, GLenum};
 
+use error;
+
 pub enum QueryTarget {
     SamplesPassed,
+    /// Primitives sent down the pipeline, captured or not
+    PrimitivesGenerated,
+    /// Primitives captured by transform feedback
+    TransformFeedbackPrimitivesWritten,
 }
 
 impl QueryTarget {
     pub fn to_glenum(&self) -> GLenum {
         match *self {
             SamplesPassed => gl::SAMPLES_PASSED,
+            PrimitivesGenerated => gl::PRIMITIVES_GENERATED,
+            TransformFeedbackPrimitivesWritten => gl::TRANSFORM_FEEDBACK_PRIMITIVES_WRITTEN,
         }
     }
 }
@@ -25,6 +33,7 @@ impl Query {
         unsafe {
             gl::GenQueries(1, &mut name);
         }
+        error::check_or_fail("Query::new");
 
         Query {
             name: name,
@@ -34,10 +43,12 @@ impl Query {
 
     pub fn begin(&self) {
         gl::BeginQuery(self.target, self.name);
+        error::check_or_fail("Query::begin");
     }
 
     pub fn end(&self) {
         gl::EndQuery(self.target);
+        error::check_or_fail("Query::end");
     }
 
     pub fn result_available(&self) -> bool {
@@ -45,6 +56,7 @@ impl Query {
         unsafe {
             gl::GetQueryObjectuiv(self.name, gl::QUERY_RESULT_AVAILABLE, &mut params);
         }
+        error::check_or_fail("Query::result_available");
         params == gl::TRUE as u32
     }
 
@@ -53,6 +65,7 @@ impl Query {
         unsafe {
             gl::GetQueryObjectuiv(self.name, gl::QUERY_RESULT, &mut params);
         }
+        error::check_or_fail("Query::result");
         params
     }
 }
@@ -0,0 +1,109 @@
+//! Rebuilding programs when their shader files change on disk.
+
+use std::io::fs;
+
+use error::Error;
+use program::{Program, Shader, ShaderType, Preprocessor};
+
+/// A program built from shader files, which is rebuilt by `poll` whenever
+/// one of the files, or anything they include, is modified.
+pub struct ReloadableProgram {
+    stages: Vec<(Path, ShaderType)>,
+    preprocessor: Preprocessor,
+    program: Program,
+    /// Every file the program depends on, with its modification time (None
+    /// if it couldn't be read).
+    files: Vec<(Path, Option<u64>)>,
+}
+
+fn modified(path: &Path) -> Option<u64> {
+    fs::stat(path).ok().map(|s| s.modified)
+}
+
+fn stamp(paths: Vec<Path>) -> Vec<(Path, Option<u64>)> {
+    paths.into_iter().map(|p| { let m = modified(&p); (p, m) }).collect()
+}
+
+/// Build the program, also returning every file that went into it.
+fn build(stages: &[(Path, ShaderType)],
+         pre: &Preprocessor) -> (Result<Program, Error>, Vec<Path>) {
+    let mut files = Vec::new();
+    let mut shaders = Vec::with_capacity(stages.len());
+    for &(ref path, type_) in stages.iter() {
+        match pre.process_file(path) {
+            Ok(source) => {
+                for &(ref p, _) in source.files.iter() {
+                    if !files.contains(p) {
+                        files.push(p.clone());
+                    }
+                }
+                shaders.push(Shader::compile_source(&source, type_));
+            },
+            Err(e) => {
+                if !files.contains(path) {
+                    files.push(path.clone());
+                }
+                return (Err(e), files);
+            }
+        }
+    }
+    (Program::link(shaders.as_slice()), files)
+}
+
+impl ReloadableProgram {
+    /// Build a program from `stages`, which are pairs of a file path and the
+    /// type of shader it contains.
+    pub fn new(stages: &[(&str, ShaderType)]) -> Result<ReloadableProgram, Error> {
+        ReloadableProgram::with_preprocessor(stages, Preprocessor::new())
+    }
+
+    /// As `new`, but preprocessing each file with `pre`.
+    pub fn with_preprocessor(stages: &[(&str, ShaderType)],
+                             pre: Preprocessor) -> Result<ReloadableProgram, Error> {
+        let stages: Vec<(Path, ShaderType)> = stages.iter().map(|&(p, t)| (Path::new(p), t)).collect();
+        let (program, files) = build(stages.as_slice(), &pre);
+        let program = try!(program);
+        Ok(ReloadableProgram {
+            stages: stages,
+            preprocessor: pre,
+            program: program,
+            files: stamp(files),
+        })
+    }
+
+    /// The most recent program which built successfully.
+    pub fn program(&self) -> &Program {
+        &self.program
+    }
+
+    /// Rebuild the program if any of its files were modified since the last
+    /// attempt. Returns None if nothing changed, and otherwise the result of
+    /// rebuilding. If rebuilding fails the previous program is kept, and it
+    /// isn't retried until a file changes again.
+    pub fn poll(&mut self) -> Option<Result<(), Error>> {
+        if self.files.iter().all(|&(ref path, mtime)| modified(path) == mtime) {
+            return None;
+        }
+
+        let (program, files) = build(self.stages.as_slice(), &self.preprocessor);
+        match program {
+            Ok(program) => {
+                self.program = program;
+                self.files = stamp(files);
+                Some(Ok(()))
+            },
+            Err(e) => {
+                // keep watching the old files too, in case the failure was
+                // a missing include that gets restored.
+                let mut paths: Vec<Path> = self.files.iter().map(|&(ref p, _)| p.clone()).collect();
+                for p in files.into_iter() {
+                    if !paths.contains(&p) {
+                        paths.push(p);
+                    }
+                }
+                self.files = stamp(paths);
+                Some(Err(e))
+            }
+        }
+    }
+}
@@ -0,0 +1,244 @@
+//! Packing values into the std140 layout, for use with uniform blocks.
+//!
+//! `Std140` is implemented for scalars, vectors (as fixed size arrays),
+//! `Mat2`/`Mat3`/`Mat4`, and `Vec`s (as GLSL arrays) of those. Implement it
+//! for your own structs with `impl_std140!`, listing every field in the
+//! order the block declares them (leaving a field out is a compile error,
+//! but the order can't be checked):
+//!
+//!     struct Light {
+//!         position: [f32, ..3],
+//!         color: [f32, ..3],
+//!         intensity: f32,
+//!     }
+//!
+//!     impl_std140!(Light { position, color, intensity })
+//!
+//! Note that `[f32, ..4]` is a `vec4`, not a `float[4]`; use a `Vec` for
+//! GLSL arrays.
+
+use std::slice;
+use std::mem;
+use gl::types::{GLfloat, GLint, GLuint};
+
+use uniform::{Mat2, Mat3, Mat4};
+
+/// A value which can be written into a std140 uniform block.
+pub trait Std140 {
+    /// The base alignment of this value, in bytes.
+    fn std140_alignment(&self) -> uint;
+
+    /// Append this value to `w`, which is already aligned to
+    /// `std140_alignment`.
+    fn write_std140(&self, w: &mut Std140Writer);
+}
+
+/// Bytes being laid out in std140.
+pub struct Std140Writer {
+    buf: Vec<u8>,
+}
+
+impl Std140Writer {
+    pub fn new() -> Std140Writer {
+        Std140Writer { buf: Vec::new() }
+    }
+
+    /// Pad with zeros up to a multiple of `alignment` bytes.
+    pub fn align(&mut self, alignment: uint) {
+        while self.buf.len() % alignment != 0 {
+            self.buf.push(0);
+        }
+    }
+
+    /// Write `value` at the next offset suitable for it.
+    pub fn write<T: Std140>(&mut self, value: &T) {
+        self.align(value.std140_alignment());
+        value.write_std140(self);
+    }
+
+    /// Append the in-memory representation of `value`, unaligned.
+    pub fn write_raw<T>(&mut self, value: &T) {
+        let buf = &mut self.buf;
+        unsafe {
+            slice::raw::buf_as_slice(value as *const T as *const u8, mem::size_of::<T>(),
+                                     |bytes| buf.push_all(bytes));
+        }
+    }
+
+    /// The bytes written so far.
+    pub fn as_slice(&self) -> &[u8] {
+        self.buf.as_slice()
+    }
+}
+
+/// Round `n` up to a multiple of 16, the alignment of a vec4.
+fn vec4_aligned(n: uint) -> uint {
+    (n + 15) & !15
+}
+
+macro_rules! scalar(
+    ($t:ty) => (
+        impl Std140 for $t {
+            fn std140_alignment(&self) -> uint { 4 }
+            fn write_std140(&self, w: &mut Std140Writer) { w.write_raw(self) }
+        }
+    )
+)
+
+macro_rules! vector(
+    ($t:ty, $align:expr) => (
+        impl Std140 for $t {
+            fn std140_alignment(&self) -> uint { $align }
+            fn write_std140(&self, w: &mut Std140Writer) { w.write_raw(self) }
+        }
+    )
+)
+
+scalar!(GLfloat)
+scalar!(GLint)
+scalar!(GLuint)
+
+vector!([GLfloat, ..2], 8)
+vector!([GLfloat, ..3], 16)
+vector!([GLfloat, ..4], 16)
+vector!([GLint, ..2], 8)
+vector!([GLint, ..3], 16)
+vector!([GLint, ..4], 16)
+vector!([GLuint, ..2], 8)
+vector!([GLuint, ..3], 16)
+vector!([GLuint, ..4], 16)
+
+impl Std140 for bool {
+    fn std140_alignment(&self) -> uint { 4 }
+    fn write_std140(&self, w: &mut Std140Writer) {
+        w.write_raw(&(*self as GLuint))
+    }
+}
+
+// Matrices are laid out as an array of column vectors, and like every array
+// each element is padded out to a vec4.
+
+impl Std140 for Mat2 {
+    fn std140_alignment(&self) -> uint { 16 }
+    fn write_std140(&self, w: &mut Std140Writer) {
+        let Mat2(ref cols) = *self;
+        for col in cols.iter() {
+            w.write_raw(col);
+            w.align(16);
+        }
+    }
+}
+
+impl Std140 for Mat3 {
+    fn std140_alignment(&self) -> uint { 16 }
+    fn write_std140(&self, w: &mut Std140Writer) {
+        let Mat3(ref cols) = *self;
+        for col in cols.iter() {
+            w.write_raw(col);
+            w.align(16);
+        }
+    }
+}
+
+impl Std140 for Mat4 {
+    fn std140_alignment(&self) -> uint { 16 }
+    fn write_std140(&self, w: &mut Std140Writer) {
+        let Mat4(ref cols) = *self;
+        w.write_raw(cols);
+    }
+}
+
+impl<T: Std140> Std140 for Vec<T> {
+    fn std140_alignment(&self) -> uint {
+        vec4_aligned(self.as_slice().get(0).map_or(16, |t| t.std140_alignment()))
+    }
+
+    fn write_std140(&self, w: &mut Std140Writer) {
+        for elt in self.iter() {
+            w.align(16);
+            elt.write_std140(w);
+        }
+        w.align(16);
+    }
+}
+
+/// Implement `Std140` for a struct, given the names of all of its fields
+/// in the order the uniform block declares them. The struct is destructured,
+/// so a missing field doesn't compile.
+#[macro_export]
+macro_rules! impl_std140(
+    ($t:ident { $($field:ident),+ }) => (
+        impl ::hgl::std140::Std140 for $t {
+            // every member has an alignment of at most 16, which structs
+            // are rounded up to.
+            fn std140_alignment(&self) -> uint { 16 }
+
+            fn write_std140(&self, w: &mut ::hgl::std140::Std140Writer) {
+                let $t { $(ref $field),+ } = *self;
+                $( w.write($field); )+
+                w.align(16);
+            }
+        }
+    )
+)
+
+#[cfg(test)]
+mod tests {
+    use super::Std140Writer;
+    use uniform::Mat3;
+
+    /// The float `offset` bytes into what `w` has written.
+    fn float_at(w: &Std140Writer, offset: uint) -> f32 {
+        assert!(offset + 4 <= w.as_slice().len());
+        unsafe { *(w.as_slice().as_ptr().offset(offset as int) as *const f32) }
+    }
+
+    #[test]
+    fn vec3_then_float() {
+        let mut w = Std140Writer::new();
+        w.write(&[1f32, 2., 3.]);
+        w.write(&4f32);
+        assert_eq!(w.as_slice().len(), 16);
+        assert_eq!(float_at(&w, 12), 4.);
+    }
+
+    #[test]
+    fn mat3_columns() {
+        let mut w = Std140Writer::new();
+        w.write(&Mat3([[1., 2., 3.], [4., 5., 6.], [7., 8., 9.]]));
+        assert_eq!(w.as_slice().len(), 48);
+        assert_eq!(float_at(&w, 8), 3.);
+        assert_eq!(float_at(&w, 16), 4.);
+        assert_eq!(float_at(&w, 32), 7.);
+        assert_eq!(float_at(&w, 40), 9.);
+    }
+
+    #[test]
+    fn float_array() {
+        let mut w = Std140Writer::new();
+        w.write(&vec![1f32, 2., 3.]);
+        assert_eq!(w.as_slice().len(), 48);
+        assert_eq!(float_at(&w, 16), 2.);
+        assert_eq!(float_at(&w, 32), 3.);
+    }
+
+    struct Light {
+        position: [f32, ..3],
+        intensity: f32,
+        range: f32,
+    }
+
+    impl_std140!(Light { position, intensity, range })
+
+    #[test]
+    fn struct_rounded_up() {
+        let light = Light { position: [1., 2., 3.], intensity: 4., range: 5. };
+        let mut w = Std140Writer::new();
+        w.write(&light);
+        w.write(&6f32);
+        assert_eq!(float_at(&w, 12), 4.);
+        assert_eq!(float_at(&w, 16), 5.);
+        assert_eq!(float_at(&w, 32), 6.);
+        assert_eq!(w.as_slice().len(), 36);
+    }
+}
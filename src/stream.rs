@@ -0,0 +1,228 @@
+//! Streaming data that is regenerated every frame, such as UI or debug
+//! geometry, without stalling on buffers GL is still drawing from.
+//!
+//! A `StreamBuffer` is one large buffer used as a ring: each `push` writes
+//! after the previous one and returns the byte offset it wrote at, to pass
+//! to `Vao::enable_attrib` (or to turn into a `first` for draw calls).
+//!
+//!     stream.bind();
+//!     let offset = stream.push(vertices.as_slice()).unwrap();
+//!     vao.enable_attrib(&program, "position", gl::FLOAT, 2, stride, offset).unwrap();
+//!     vao.draw_array(Triangles, 0, vertices.len() as GLsizei);
+//!     // ... once the frame is done
+//!     stream.end_frame();
+
+use gl;
+use std::cmp;
+use std::mem;
+use std::ptr;
+use gl::types::{GLintptr, GLsizeiptr, GLsync};
+
+use buffer::{Buffer, BufferTarget};
+use context;
+use error;
+use error::{Error, InvalidArgument};
+
+/// Whether the current context supports persistent mapping, which
+/// `StreamBuffer` uses when it can. Requires GL 4.4 or ARB_buffer_storage.
+pub fn is_persistent_supported() -> bool {
+    context::supports_version(4, 4) || context::has_extension("GL_ARB_buffer_storage")
+}
+
+/// A ring buffer for data written once and drawn once.
+///
+/// With persistent mapping, the whole buffer stays mapped and each frame is
+/// fenced by `end_frame`; writing over a frame GL may still be drawing from
+/// waits for its fence. Otherwise, `end_frame` orphans the buffer
+/// (reallocates it with glBufferData), so GL can keep drawing from the old
+/// storage while the next frame starts at the beginning of the new one, and
+/// each push maps just the range it writes.
+///
+/// Either way, everything pushed in one frame stays valid until the frame
+/// ends, so a frame can push at most `size` bytes.
+pub struct StreamBuffer {
+    buffer: Buffer,
+    size: uint,
+    /// Total bytes pushed so far, never wrapping. The byte offset into the
+    /// buffer is this modulo `size`.
+    written: uint,
+    /// Where the current frame started, in the same units as `written`.
+    frame_start: uint,
+    /// The persistent mapping, or null when orphaning.
+    ptr: *mut u8,
+    /// The start of each fenced frame, oldest first.
+    fences: Vec<(uint, GLsync)>,
+}
+
+impl Drop for StreamBuffer {
+    fn drop(&mut self) {
+        for &(_, sync) in self.fences.iter() {
+            unsafe { gl::DeleteSync(sync); }
+        }
+        // deleting the buffer unmaps it
+    }
+}
+
+impl Deref<Buffer> for StreamBuffer {
+    fn deref<'a>(&'a self) -> &'a Buffer {
+        &self.buffer
+    }
+}
+
+/// Block until GL has passed `sync`, then delete it.
+fn wait(sync: GLsync) {
+    loop {
+        let status = unsafe { gl::ClientWaitSync(sync, gl::SYNC_FLUSH_COMMANDS_BIT, 1000000000) };
+        match status {
+            gl::ALREADY_SIGNALED | gl::CONDITION_SATISFIED => break,
+            gl::WAIT_FAILED => fail!("glClientWaitSync failed"),
+            _ => {} // timed out; keep waiting
+        }
+    }
+    unsafe { gl::DeleteSync(sync); }
+}
+
+impl StreamBuffer {
+    /// Create a stream buffer of `size` bytes bound to `target`, using
+    /// persistent mapping if the context supports it. `size` should hold a
+    /// few frames worth of data. Fails if `size` is 0, or GL refuses to map
+    /// the buffer.
+    pub fn new(target: BufferTarget, size: uint) -> Result<StreamBuffer, Error> {
+        if size == 0 {
+            return Err(InvalidArgument("stream buffer of 0 bytes".to_string()));
+        }
+        let persistent = is_persistent_supported();
+        let buffer = Buffer::new(target);
+        buffer.bind();
+        let ptr = if persistent {
+            let flags = gl::MAP_WRITE_BIT | gl::MAP_PERSISTENT_BIT | gl::MAP_COHERENT_BIT;
+            unsafe {
+                gl::BufferStorage(buffer.target, size as GLsizeiptr, ptr::null(), flags);
+            }
+            gl::MapBufferRange(buffer.target, 0, size as GLsizeiptr, flags) as *mut u8
+        } else {
+            unsafe {
+                gl::BufferData(buffer.target, size as GLsizeiptr, ptr::null(), gl::STREAM_DRAW);
+            }
+            0 as *mut u8
+        };
+        try!(error::check("StreamBuffer::new"));
+        // the storage is immutable, so falling back to orphaning isn't
+        // possible.
+        if persistent && ptr.is_null() {
+            return Err(InvalidArgument("glMapBufferRange failed".to_string()));
+        }
+
+        Ok(StreamBuffer {
+            buffer: buffer,
+            size: size,
+            written: 0,
+            frame_start: 0,
+            ptr: ptr,
+            fences: Vec::new(),
+        })
+    }
+
+    /// Whether this buffer is persistently mapped, rather than orphaned.
+    pub fn is_persistent(&self) -> bool {
+        !self.ptr.is_null()
+    }
+
+    /// The size of the buffer in bytes.
+    pub fn size(&self) -> uint {
+        self.size
+    }
+
+    /// Write `data` after whatever was pushed before, wrapping around to the
+    /// start of the buffer when it doesn't fit before the end, and return the
+    /// byte offset it was written at. This binds the buffer.
+    ///
+    /// Fails if this frame would then have pushed more than the whole
+    /// buffer, or GL refuses to map the range.
+    pub fn push<T>(&mut self, data: &[T]) -> Result<uint, Error> {
+        let bytes = data.len() * mem::size_of::<T>();
+        if bytes > self.size {
+            return Err(InvalidArgument(format!("{} bytes don't fit in a stream buffer of {}",
+                                               bytes, self.size)));
+        }
+
+        let align = cmp::max(mem::min_align_of::<T>(), 4);
+        let mut start = (self.written + align - 1) / align * align;
+        if start % self.size + bytes > self.size {
+            start = (start / self.size + 1) * self.size;
+        }
+        // when orphaning, frames start at the beginning of the buffer, so
+        // this also catches wrapping around within a frame.
+        if start + bytes - self.frame_start > self.size {
+            return Err(InvalidArgument(format!("a frame pushed more than the {} bytes of its \
+                                                stream buffer", self.size)));
+        }
+        let offset = start % self.size;
+
+        self.buffer.bind();
+        if self.is_persistent() {
+            self.wait_for(start + bytes);
+            unsafe {
+                ptr::copy_nonoverlapping_memory(self.ptr.offset(offset as int) as *mut T,
+                                                data.as_ptr(), data.len());
+            }
+        } else if bytes > 0 {
+            // this range hasn't been written since the last orphan, so GL
+            // can't be using it.
+            let flags = gl::MAP_WRITE_BIT | gl::MAP_INVALIDATE_RANGE_BIT | gl::MAP_UNSYNCHRONIZED_BIT;
+            let dest = gl::MapBufferRange(self.buffer.target, offset as GLintptr,
+                                          bytes as GLsizeiptr, flags) as *mut T;
+            if dest.is_null() {
+                try!(error::check("StreamBuffer::push"));
+                return Err(InvalidArgument("glMapBufferRange failed".to_string()));
+            }
+            unsafe { ptr::copy_nonoverlapping_memory(dest, data.as_ptr(), data.len()); }
+            gl::UnmapBuffer(self.buffer.target);
+        }
+        self.written = start + bytes;
+        try!(error::check("StreamBuffer::push"));
+        Ok(offset)
+    }
+
+    /// Mark the end of a frame. Call this once the draw calls using the
+    /// frame's data have been made.
+    pub fn end_frame(&mut self) {
+        if self.written > self.frame_start {
+            if self.is_persistent() {
+                let sync = gl::FenceSync(gl::SYNC_GPU_COMMANDS_COMPLETE, 0);
+                self.fences.push((self.frame_start, sync));
+            } else {
+                self.orphan();
+                let size = self.size;
+                self.written = (self.written + size - 1) / size * size;
+            }
+        }
+        self.frame_start = self.written;
+        error::check_or_fail("StreamBuffer::end_frame");
+    }
+
+    /// Give the buffer new storage, leaving the old one to GL until it is
+    /// done drawing from it.
+    fn orphan(&self) {
+        self.buffer.bind();
+        unsafe {
+            gl::BufferData(self.buffer.target, self.size as GLsizeiptr,
+                           ptr::null(), gl::STREAM_DRAW);
+        }
+    }
+
+    /// Wait for every frame whose data would be overwritten by writing up to
+    /// `end` (in the units of `written`).
+    fn wait_for(&mut self, end: uint) {
+        let size = self.size;
+        // writing up to `end` overwrites what was written up to `end - size`
+        self.fences.retain(|&(start, sync)| {
+            if start + size < end {
+                wait(sync);
+                false
+            } else {
+                true
+            }
+        });
+    }
+}
@@ -1,6 +1,10 @@
 use gl;
 use gl::types::{GLuint, GLenum, GLsizei, GLfloat, GLvoid, GLint};
 
+use debug;
+use error;
+use error::{Error, InvalidArgument};
+
 #[path="pixel.rs"]
 pub mod pixel;
 
@@ -230,6 +234,15 @@ impl SubImageInfo {
     }
 }
 
+/// Unwrap a dimension or offset of an image, which `msg` explains the need
+/// for.
+fn require(value: Option<GLsizei>, msg: &str) -> Result<GLsizei, Error> {
+    match value {
+        Some(v) => Ok(v),
+        None => Err(InvalidArgument(msg.to_string()))
+    }
+}
+
 /// A texture object.
 pub struct Texture {
     pub name: GLuint,
@@ -240,25 +253,34 @@ impl Texture {
     /// Create a new texture and load an image into it.  Note that even if
     /// your data isn't GL_BYTE, you can pass a *u8 anyway since the GL
     /// doesn't care about the type.
-    pub fn new(target: TextureTarget, info: ImageInfo, data: *const u8) -> Texture {
+    pub fn new(target: TextureTarget, info: ImageInfo, data: *const u8) -> Result<Texture, Error> {
         let mut tex: GLuint = 0;
         unsafe { gl::GenTextures(1, &mut tex as *mut GLuint); }
         let t = Texture { name: tex, target: target.to_glenum() };
         t.bind();
-        t.load_image(info, data);
+        try!(t.load_image(info, data));
         t.gen_mipmaps();
-        t
+        Ok(t)
     }
 
     /// Create a texture without binding it.
     pub fn new_raw(target: TextureTarget) -> Texture {
         let mut tex: GLuint = 0;
         unsafe { gl::GenTextures(1, &mut tex as *mut GLuint); }
+        error::check_or_fail("Texture::new_raw");
         Texture { name: tex, target: target.to_glenum() }
     }
 
     pub fn bind(&self) {
         gl::BindTexture(self.target, self.name);
+        error::check_or_fail("Texture::bind");
+    }
+
+    /// Label this texture for debug messages and debuggers. This binds it.
+    /// Requires GL 4.3 or KHR_debug.
+    pub fn label(&self, label: &str) {
+        self.bind();
+        debug::object_label(gl::TEXTURE, self.name, label);
     }
 
     pub fn wrap(&self, w: WrapMode) {
@@ -270,16 +292,19 @@ impl Texture {
     pub fn wrap_s(&self, w: WrapMode) {
         self.bind();
         gl::TexParameteri(self.target, gl::TEXTURE_WRAP_S, w.to_glenum() as GLint);
+        error::check_or_fail("Texture::wrap_s");
     }
 
     pub fn wrap_t(&self, w: WrapMode) {
         self.bind();
         gl::TexParameteri(self.target, gl::TEXTURE_WRAP_T, w.to_glenum() as GLint);
+        error::check_or_fail("Texture::wrap_t");
     }
 
     pub fn wrap_r(&self, w: WrapMode) {
         self.bind();
         gl::TexParameteri(self.target, gl::TEXTURE_WRAP_R, w.to_glenum() as GLint);
+        error::check_or_fail("Texture::wrap_r");
     }
 
     pub fn border_color(&self, color: &[GLfloat]) {
@@ -287,6 +312,7 @@ impl Texture {
         unsafe {
             gl::TexParameterfv(self.target, gl::TEXTURE_BORDER_COLOR, color.as_ptr());
         }
+        error::check_or_fail("Texture::border_color");
     }
 
     pub fn filter(&self, fm: FilterMethod) {
@@ -297,21 +323,25 @@ impl Texture {
     pub fn min_filter(&self, fm: FilterMethod) {
         self.bind();
         gl::TexParameteri(self.target, gl::TEXTURE_MIN_FILTER, fm.to_glenum() as GLint);
+        error::check_or_fail("Texture::min_filter");
     }
 
     pub fn mag_filter(&self, fm: FilterMethod) {
         self.bind();
         gl::TexParameteri(self.target, gl::TEXTURE_MAG_FILTER, fm.to_glenum() as GLint);
+        error::check_or_fail("Texture::mag_filter");
     }
 
     /// SAFETY NOTE: You *must* call `load_data` before calling this method.
     /// Bad Things will happen otherwise.
     pub fn gen_mipmaps(&self) {
         gl::GenerateMipmap(self.target);
+        error::check_or_fail("Texture::gen_mipmaps");
     }
 
-    /// Load an image into this texture.
-    pub fn load_image(&self, info: ImageInfo, data: *const u8) {
+    /// Load an image into this texture. Fails without uploading anything if
+    /// `info` is missing a dimension the image needs.
+    pub fn load_image(&self, info: ImageInfo, data: *const u8) -> Result<(), Error> {
         self.bind();
 
         let ImageInfo { level, internal_format, width, height, depth, format, ptype } = info;
@@ -321,27 +351,29 @@ impl Texture {
             if height.is_none() { unsafe {
                 // 1D
                 gl::TexImage1D(self.target, level, internal_format,
-                               width.expect("1D texture needs a width!"),
+                               try!(require(width, "1D texture needs a width")),
                                0, format, ptype, data as *const GLvoid);
             } } else { unsafe {
                 // 2D
                 gl::TexImage2D(self.target, level, internal_format,
-                               width.expect("2D texture needs a width!"),
-                               height.expect("2D texture needs a height!"),
+                               try!(require(width, "2D texture needs a width")),
+                               try!(require(height, "2D texture needs a height")),
                                0, format, ptype, data as *const GLvoid);
             } }
         } else { unsafe {
             // 3D
             gl::TexImage3D(self.target, level, internal_format,
-                           width.expect("3D texture needs a width!"),
-                           height.expect("3D texture needs a height!"),
-                           depth.expect("3D texture needs a depth!"),
+                           try!(require(width, "3D texture needs a width")),
+                           try!(require(height, "3D texture needs a height")),
+                           try!(require(depth, "3D texture needs a depth")),
                            0, format, ptype, data as *const GLvoid);
         } }
+        error::check("Texture::load_image")
     }
 
-    /// Load an image into part of this texture.
-    pub fn load_subimage(&self, info: SubImageInfo, data: *const u8) {
+    /// Load an image into part of this texture. Fails without uploading anything
+    /// if `info` is missing a dimension or offset the image needs.
+    pub fn load_subimage(&self, info: SubImageInfo, data: *const u8) -> Result<(), Error> {
         self.bind();
 
         let SubImageInfo { level, width, height, depth, xoffset, yoffset, zoffset, format, ptype } = info;
@@ -352,34 +384,36 @@ impl Texture {
             if height.is_none() && yoffset.is_none() { unsafe {
                 // 1D
                 gl::TexSubImage1D(self.target, level,
-                                  xoffset.expect("1D texture needs an xoffset!"),
-                                  width.expect("1D texture needs a width!"),
+                                  try!(require(xoffset, "1D texture needs an xoffset")),
+                                  try!(require(width, "1D texture needs a width")),
                                   format, ptype, data as *const GLvoid);
             } } else { unsafe {
                 // 2D
                 gl::TexSubImage2D(self.target, level,
-                                  xoffset.expect("2D texture needs an xoffset!"),
-                                  yoffset.expect("2D texture needs a yoffset!"),
-                                  width.expect("2D texture needs a width!"),
-                                  height.expect("2D texture needs a height!"),
+                                  try!(require(xoffset, "2D texture needs an xoffset")),
+                                  try!(require(yoffset, "2D texture needs a yoffset")),
+                                  try!(require(width, "2D texture needs a width")),
+                                  try!(require(height, "2D texture needs a height")),
                                   format, ptype, data as *const GLvoid);
             } }
         } else { unsafe {
             // 3D
             gl::TexSubImage3D(self.target, level,
-                              xoffset.expect("3D texture needs an xoffset!"),
-                              yoffset.expect("3D texture needs a yoffset!"),
-                              zoffset.expect("3D texture needs a zoffset!"),
-                              width.expect("3D texture needs a width!"),
-                              height.expect("3D texture needs a height!"),
-                              depth.expect("3D texture needs a height!"),
+                              try!(require(xoffset, "3D texture needs an xoffset")),
+                              try!(require(yoffset, "3D texture needs a yoffset")),
+                              try!(require(zoffset, "3D texture needs a zoffset")),
+                              try!(require(width, "3D texture needs a width")),
+                              try!(require(height, "3D texture needs a height")),
+                              try!(require(depth, "3D texture needs a depth")),
                               format, ptype, data as *const GLvoid);
         } }
+        error::check("Texture::load_subimage")
     }
 
     /// Bind this texture to texture unit `num` (GL_TEXTURE0 + num)
     pub fn activate(&self, num: GLuint) {
         gl::ActiveTexture(gl::TEXTURE0 + num);
+        error::check_or_fail("Texture::activate");
         self.bind();
     }
 }
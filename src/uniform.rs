@@ -0,0 +1,276 @@
+//! Typed uniform values, for use with `Program::set_uniform`.
+
+use gl;
+use gl::types::{GLint, GLuint, GLfloat};
+
+/// A value which can be uploaded to a uniform location.
+pub trait Uniform {
+    /// Upload this value to `location` in the currently bound program.
+    fn set(&self, location: GLint);
+}
+
+/// A texture unit, to be assigned to a sampler uniform. This is the same
+/// number passed to `Texture::activate`.
+pub struct Sampler(pub GLuint);
+
+/// A 2x2 matrix, stored column-major.
+pub struct Mat2(pub [[GLfloat, ..2], ..2]);
+
+/// A 3x3 matrix, stored column-major.
+pub struct Mat3(pub [[GLfloat, ..3], ..3]);
+
+/// A 4x4 matrix, stored column-major.
+pub struct Mat4(pub [[GLfloat, ..4], ..4]);
+
+/// A matrix (or slice of matrices) stored row-major, which GL will transpose
+/// when uploading.
+pub struct Transpose<M>(pub M);
+
+impl Uniform for GLfloat {
+    fn set(&self, location: GLint) {
+        gl::Uniform1f(location, *self);
+    }
+}
+
+impl Uniform for GLint {
+    fn set(&self, location: GLint) {
+        gl::Uniform1i(location, *self);
+    }
+}
+
+impl Uniform for GLuint {
+    fn set(&self, location: GLint) {
+        gl::Uniform1ui(location, *self);
+    }
+}
+
+impl Uniform for bool {
+    fn set(&self, location: GLint) {
+        gl::Uniform1i(location, *self as GLint);
+    }
+}
+
+impl Uniform for Sampler {
+    fn set(&self, location: GLint) {
+        let Sampler(unit) = *self;
+        gl::Uniform1i(location, unit as GLint);
+    }
+}
+
+impl Uniform for [GLfloat, ..2] {
+    fn set(&self, location: GLint) {
+        unsafe { gl::Uniform2fv(location, 1, self.as_slice().as_ptr()); }
+    }
+}
+
+impl Uniform for [GLfloat, ..3] {
+    fn set(&self, location: GLint) {
+        unsafe { gl::Uniform3fv(location, 1, self.as_slice().as_ptr()); }
+    }
+}
+
+impl Uniform for [GLfloat, ..4] {
+    fn set(&self, location: GLint) {
+        unsafe { gl::Uniform4fv(location, 1, self.as_slice().as_ptr()); }
+    }
+}
+
+impl Uniform for [GLint, ..2] {
+    fn set(&self, location: GLint) {
+        unsafe { gl::Uniform2iv(location, 1, self.as_slice().as_ptr()); }
+    }
+}
+
+impl Uniform for [GLint, ..3] {
+    fn set(&self, location: GLint) {
+        unsafe { gl::Uniform3iv(location, 1, self.as_slice().as_ptr()); }
+    }
+}
+
+impl Uniform for [GLint, ..4] {
+    fn set(&self, location: GLint) {
+        unsafe { gl::Uniform4iv(location, 1, self.as_slice().as_ptr()); }
+    }
+}
+
+impl Uniform for [GLuint, ..2] {
+    fn set(&self, location: GLint) {
+        unsafe { gl::Uniform2uiv(location, 1, self.as_slice().as_ptr()); }
+    }
+}
+
+impl Uniform for [GLuint, ..3] {
+    fn set(&self, location: GLint) {
+        unsafe { gl::Uniform3uiv(location, 1, self.as_slice().as_ptr()); }
+    }
+}
+
+impl Uniform for [GLuint, ..4] {
+    fn set(&self, location: GLint) {
+        unsafe { gl::Uniform4uiv(location, 1, self.as_slice().as_ptr()); }
+    }
+}
+
+impl Uniform for Mat2 {
+    fn set(&self, location: GLint) {
+        let Mat2(ref m) = *self;
+        unsafe { gl::UniformMatrix2fv(location, 1, gl::FALSE, m.as_ptr() as *const GLfloat); }
+    }
+}
+
+impl Uniform for Mat3 {
+    fn set(&self, location: GLint) {
+        let Mat3(ref m) = *self;
+        unsafe { gl::UniformMatrix3fv(location, 1, gl::FALSE, m.as_ptr() as *const GLfloat); }
+    }
+}
+
+impl Uniform for Mat4 {
+    fn set(&self, location: GLint) {
+        let Mat4(ref m) = *self;
+        unsafe { gl::UniformMatrix4fv(location, 1, gl::FALSE, m.as_ptr() as *const GLfloat); }
+    }
+}
+
+// Arrays of values. The element count is taken from the slice length.
+
+impl<'a> Uniform for &'a [GLfloat] {
+    fn set(&self, location: GLint) {
+        unsafe { gl::Uniform1fv(location, self.len() as GLint, self.as_ptr()); }
+    }
+}
+
+impl<'a> Uniform for &'a [GLint] {
+    fn set(&self, location: GLint) {
+        unsafe { gl::Uniform1iv(location, self.len() as GLint, self.as_ptr()); }
+    }
+}
+
+impl<'a> Uniform for &'a [GLuint] {
+    fn set(&self, location: GLint) {
+        unsafe { gl::Uniform1uiv(location, self.len() as GLint, self.as_ptr()); }
+    }
+}
+
+impl<'a> Uniform for &'a [[GLfloat, ..2]] {
+    fn set(&self, location: GLint) {
+        unsafe { gl::Uniform2fv(location, self.len() as GLint, self.as_ptr() as *const GLfloat); }
+    }
+}
+
+impl<'a> Uniform for &'a [[GLfloat, ..3]] {
+    fn set(&self, location: GLint) {
+        unsafe { gl::Uniform3fv(location, self.len() as GLint, self.as_ptr() as *const GLfloat); }
+    }
+}
+
+impl<'a> Uniform for &'a [[GLfloat, ..4]] {
+    fn set(&self, location: GLint) {
+        unsafe { gl::Uniform4fv(location, self.len() as GLint, self.as_ptr() as *const GLfloat); }
+    }
+}
+
+impl<'a> Uniform for &'a [[GLint, ..2]] {
+    fn set(&self, location: GLint) {
+        unsafe { gl::Uniform2iv(location, self.len() as GLint, self.as_ptr() as *const GLint); }
+    }
+}
+
+impl<'a> Uniform for &'a [[GLint, ..3]] {
+    fn set(&self, location: GLint) {
+        unsafe { gl::Uniform3iv(location, self.len() as GLint, self.as_ptr() as *const GLint); }
+    }
+}
+
+impl<'a> Uniform for &'a [[GLint, ..4]] {
+    fn set(&self, location: GLint) {
+        unsafe { gl::Uniform4iv(location, self.len() as GLint, self.as_ptr() as *const GLint); }
+    }
+}
+
+impl<'a> Uniform for &'a [[GLuint, ..2]] {
+    fn set(&self, location: GLint) {
+        unsafe { gl::Uniform2uiv(location, self.len() as GLint, self.as_ptr() as *const GLuint); }
+    }
+}
+
+impl<'a> Uniform for &'a [[GLuint, ..3]] {
+    fn set(&self, location: GLint) {
+        unsafe { gl::Uniform3uiv(location, self.len() as GLint, self.as_ptr() as *const GLuint); }
+    }
+}
+
+impl<'a> Uniform for &'a [[GLuint, ..4]] {
+    fn set(&self, location: GLint) {
+        unsafe { gl::Uniform4uiv(location, self.len() as GLint, self.as_ptr() as *const GLuint); }
+    }
+}
+
+impl<'a> Uniform for &'a [Sampler] {
+    fn set(&self, location: GLint) {
+        let units: Vec<GLint> = self.iter().map(|&Sampler(u)| u as GLint).collect();
+        unsafe { gl::Uniform1iv(location, units.len() as GLint, units.as_ptr()); }
+    }
+}
+
+impl<'a> Uniform for &'a [Mat2] {
+    fn set(&self, location: GLint) {
+        unsafe { gl::UniformMatrix2fv(location, self.len() as GLint, gl::FALSE, self.as_ptr() as *const GLfloat); }
+    }
+}
+
+impl<'a> Uniform for &'a [Mat3] {
+    fn set(&self, location: GLint) {
+        unsafe { gl::UniformMatrix3fv(location, self.len() as GLint, gl::FALSE, self.as_ptr() as *const GLfloat); }
+    }
+}
+
+impl<'a> Uniform for &'a [Mat4] {
+    fn set(&self, location: GLint) {
+        unsafe { gl::UniformMatrix4fv(location, self.len() as GLint, gl::FALSE, self.as_ptr() as *const GLfloat); }
+    }
+}
+
+// Row-major matrices.
+
+impl Uniform for Transpose<Mat2> {
+    fn set(&self, location: GLint) {
+        let Transpose(Mat2(ref m)) = *self;
+        unsafe { gl::UniformMatrix2fv(location, 1, gl::TRUE, m.as_ptr() as *const GLfloat); }
+    }
+}
+
+impl Uniform for Transpose<Mat3> {
+    fn set(&self, location: GLint) {
+        let Transpose(Mat3(ref m)) = *self;
+        unsafe { gl::UniformMatrix3fv(location, 1, gl::TRUE, m.as_ptr() as *const GLfloat); }
+    }
+}
+
+impl Uniform for Transpose<Mat4> {
+    fn set(&self, location: GLint) {
+        let Transpose(Mat4(ref m)) = *self;
+        unsafe { gl::UniformMatrix4fv(location, 1, gl::TRUE, m.as_ptr() as *const GLfloat); }
+    }
+}
+
+impl<'a> Uniform for Transpose<&'a [Mat2]> {
+    fn set(&self, location: GLint) {
+        let Transpose(m) = *self;
+        unsafe { gl::UniformMatrix2fv(location, m.len() as GLint, gl::TRUE, m.as_ptr() as *const GLfloat); }
+    }
+}
+
+impl<'a> Uniform for Transpose<&'a [Mat3]> {
+    fn set(&self, location: GLint) {
+        let Transpose(m) = *self;
+        unsafe { gl::UniformMatrix3fv(location, m.len() as GLint, gl::TRUE, m.as_ptr() as *const GLfloat); }
+    }
+}
+
+impl<'a> Uniform for Transpose<&'a [Mat4]> {
+    fn set(&self, location: GLint) {
+        let Transpose(m) = *self;
+        unsafe { gl::UniformMatrix4fv(location, m.len() as GLint, gl::TRUE, m.as_ptr() as *const GLfloat); }
+    }
+}
@@ -1,9 +1,23 @@
 use gl;
 use libc::c_void;
+use std::mem::size_of;
 use gl::types::{GLint, GLuint, GLsizei, GLvoid, GLenum};
 
 use Program;
 use Primitive;
+use buffer::TypedBuffer;
+use debug;
+use error;
+use error::{Error, InvalidArgument};
+use program;
+
+/// The location of the active attribute `name` in `program`.
+fn attrib_location(program: &Program, name: &str) -> Result<GLuint, Error> {
+    match program.attrib(name) {
+        -1 => Err(InvalidArgument(format!("no active attribute named {}", name))),
+        pos => Ok(pos as GLuint)
+    }
+}
 
 /// A vertex array object
 pub struct Vao {
@@ -20,71 +34,82 @@ impl Vao {
     pub fn new() -> Vao {
         let mut vao: GLuint = 0;
         unsafe { gl::GenVertexArrays(1, &mut vao as *mut GLuint); }
+        error::check_or_fail("Vao::new");
         Vao { name: vao }
     }
 
     pub fn bind(&self) {
         gl::BindVertexArray(self.name);
+        error::check_or_fail("Vao::bind");
+    }
+
+    /// Label this VAO for debug messages and debuggers. This binds it.
+    /// Requires GL 4.3 or KHR_debug.
+    pub fn label(&self, label: &str) {
+        self.bind();
+        debug::object_label(gl::VERTEX_ARRAY, self.name, label);
     }
 
     /// Define and enable an array of generic vertex attribute data for `name`
     /// in `program`, in this VAO, using the bound VBO.  TODO: Normalize
     /// hardcoded to GL_FALSE.
     ///
+    /// Fails if `program` has no active attribute `name`, which includes
+    /// attributes the GLSL compiler optimized away.
+    ///
     /// NOTE: Memory unsafety caused when no bound VBO, or bound VBO does not
-    /// have enough data.
+    /// have enough data. Drawing with `draw_buffer` catches the latter in
+    /// debug builds.
     pub fn enable_attrib(&self, program: &Program, name: &str, type_: GLenum,
-                         elts: GLint, stride: GLint, offset: uint) {
+                         elts: GLint, stride: GLint, offset: uint) -> Result<(), Error> {
+        let pos = try!(attrib_location(program, name));
         self.bind();
-        name.with_c_str(|cstr| {
-            unsafe {
-                let pos = gl::GetAttribLocation(program.get_name(), cstr);
-                gl::EnableVertexAttribArray(pos as GLuint);
-                gl::VertexAttribPointer(pos as GLuint, elts, type_,
-                                        gl::FALSE, stride, offset as *const c_void);
-            }
-        });
+        gl::EnableVertexAttribArray(pos);
+        unsafe {
+            gl::VertexAttribPointer(pos, elts, type_,
+                                    gl::FALSE, stride, offset as *const c_void);
+        }
+        error::check("Vao::enable_attrib")
     }
 
     /// As enable_attrib, but using glVertexAttribIPointer
     pub fn enable_int_attrib(&self, program: &Program, name: &str, type_: GLenum,
-                         elts: GLint, stride: GLint, offset: uint) {
+                         elts: GLint, stride: GLint, offset: uint) -> Result<(), Error> {
+        let pos = try!(attrib_location(program, name));
         self.bind();
-        name.with_c_str(|cstr| {
-            unsafe {
-                let pos = gl::GetAttribLocation(program.get_name(), cstr);
-                gl::EnableVertexAttribArray(pos as GLuint);
-                gl::VertexAttribIPointer(pos as GLuint, elts, type_,
-                                         stride, offset as *const c_void);
-            }
-        });
+        gl::EnableVertexAttribArray(pos);
+        unsafe {
+            gl::VertexAttribIPointer(pos, elts, type_,
+                                     stride, offset as *const c_void);
+        }
+        error::check("Vao::enable_int_attrib")
     }
 
     /// As enable_attrib, but using glVertexAttribLPointer
     pub fn enable_double_attrib(&self, program: &Program, name: &str,
-                         elts: GLint, stride: GLint, offset: uint) {
+                         elts: GLint, stride: GLint, offset: uint) -> Result<(), Error> {
+        let pos = try!(attrib_location(program, name));
         self.bind();
-        name.with_c_str(|cstr| {
-            unsafe {
-                let pos = gl::GetAttribLocation(program.get_name(), cstr);
-                gl::EnableVertexAttribArray(pos as GLuint);
-                gl::VertexAttribLPointer(pos as GLuint, elts, gl::DOUBLE, stride, offset as *const c_void);
-            }
-        });
+        gl::EnableVertexAttribArray(pos);
+        unsafe {
+            gl::VertexAttribLPointer(pos, elts, gl::DOUBLE, stride, offset as *const c_void);
+        }
+        error::check("Vao::enable_double_attrib")
     }
 
-    pub fn disable_attrib(&self, program: &Program, name: &str) {
+    pub fn disable_attrib(&self, program: &Program, name: &str) -> Result<(), Error> {
+        let pos = try!(attrib_location(program, name));
         self.bind();
-        name.with_c_str(|cstr| {
-            let pos = unsafe { gl::GetAttribLocation(program.get_name(), cstr) };
-            gl::DisableVertexAttribArray(pos as GLuint);
-        });
+        gl::DisableVertexAttribArray(pos);
+        error::check("Vao::disable_attrib")
     }
 
     /// Draw the given primitive, using `count` vertices starting at offset
     /// `first` in the currently bound VBO.
     pub fn draw_array(&self, primitive: Primitive, first: GLint, count: GLsizei) {
+        program::validate_for_draw();
         gl::DrawArrays(primitive.to_glenum(), first, count);
+        error::check_or_fail("Vao::draw_array");
     }
 
     /// Draw the given primitive, using `count` vertices starting at offset
@@ -92,9 +117,45 @@ impl Vao {
     ///
     /// TODO: Hardcoded to GL_UNSIGNED_INT
     pub fn draw_elements(&self, primitive: Primitive, first: GLint, count: GLint) {
+        program::validate_for_draw();
         // last argument null; use the bound buffer
         unsafe {
             gl::DrawElements(primitive.to_glenum(), count, gl::UNSIGNED_INT, first as *const GLvoid);
         }
+        error::check_or_fail("Vao::draw_elements");
+    }
+
+    /// As `draw_array`, but in debug builds failing if the vertices drawn
+    /// aren't all in `vertices`, which must hold one `V` per vertex.
+    pub fn draw_buffer<V>(&self, primitive: Primitive, vertices: &TypedBuffer<V>,
+                          first: GLint, count: GLsizei) {
+        if cfg!(not(ndebug)) {
+            check_range("draw_buffer", first, count, vertices.len());
+        }
+        self.draw_array(primitive, first, count);
+    }
+
+    /// Draw the given primitive using `count` indices starting at index
+    /// `first` (not a byte offset, unlike `draw_elements`) of `indices`,
+    /// which must be the EBO bound to this VAO. In debug builds, fails if
+    /// the indices drawn aren't all in `indices`.
+    pub fn draw_indices(&self, primitive: Primitive, indices: &TypedBuffer<GLuint>,
+                        first: GLint, count: GLsizei) {
+        if cfg!(not(ndebug)) {
+            check_range("draw_indices", first, count, indices.len());
+        }
+        program::validate_for_draw();
+        let offset = first as uint * size_of::<GLuint>();
+        unsafe {
+            gl::DrawElements(primitive.to_glenum(), count, gl::UNSIGNED_INT, offset as *const GLvoid);
+        }
+        error::check_or_fail("Vao::draw_indices");
+    }
+}
+
+/// Fail unless `first .. first + count` lies within `len` elements.
+fn check_range(func: &str, first: GLint, count: GLsizei, len: uint) {
+    if first < 0 || count < 0 || first as uint + count as uint > len {
+        fail!("{}: drawing {} elements from {} overruns a buffer of {}", func, count, first, len);
     }
 }